@@ -0,0 +1,230 @@
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+
+use crate::path_filter::PathFilter;
+use crate::util::normalize;
+
+/// Name of the audit log file, created inside of the save data directory.
+const AUDIT_LOG_FILE_NAME: &str = "itb_io_audit.log";
+/// Size the audit log is allowed to grow to before it is rotated, unless specified otherwise.
+const DEFAULT_MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+lazy_static! {
+    static ref AUDIT_LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+}
+
+/// Single mutating operation performed on a file or a directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub operation: String,
+    pub path: String,
+    /// Destination of operations which have one, like copying or moving
+    pub destination: Option<String>,
+    /// Number of bytes written, for operations which write content
+    pub bytes: Option<u64>,
+    /// Error message, if the operation failed
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.timestamp,
+            self.operation,
+            self.bytes.map(|bytes| bytes.to_string()).unwrap_or_else(|| "-".to_string()),
+            self.error.as_deref().map(sanitize).unwrap_or_else(|| "ok".to_string()),
+            sanitize(&self.path),
+            self.destination.as_deref().map(sanitize).unwrap_or_default(),
+        )
+    }
+
+    fn from_line(line: &str) -> Option<AuditEntry> {
+        let mut fields = line.splitn(6, '\t');
+        let timestamp = fields.next()?.parse().ok()?;
+        let operation = fields.next()?.to_string();
+        let bytes = match fields.next()? {
+            "-" => None,
+            bytes => Some(bytes.parse().ok()?)
+        };
+        let error = match fields.next()? {
+            "ok" => None,
+            error => Some(error.to_string())
+        };
+        let path = fields.next()?.to_string();
+        let destination = match fields.next()? {
+            "" => None,
+            destination => Some(destination.to_string())
+        };
+
+        Some(AuditEntry { timestamp, operation, path, destination, bytes, error })
+    }
+}
+
+/// Keeps every field on a single line and unambiguously separated.
+fn sanitize(value: &str) -> String {
+    value.replace(['\t', '\r', '\n'], " ")
+}
+
+/// Log file which is rotated once it exceeds its maximum size, keeping a single previous log.
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+}
+
+impl AuditLog {
+    pub fn new<P: AsRef<Path>>(path: P, max_size: u64) -> AuditLog {
+        AuditLog {
+            path: path.as_ref().to_path_buf(),
+            max_size,
+        }
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut file_name = self.path.file_name().unwrap().to_os_string();
+        file_name.push(".1");
+        self.path.with_file_name(file_name)
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> std::io::Result<()> {
+        let line = entry.to_line() + "\n";
+
+        let current_size = std::fs::metadata(&self.path).map(|it| it.len()).unwrap_or(0);
+        if current_size > 0 && current_size + line.len() as u64 > self.max_size {
+            std::fs::rename(&self.path, self.rotated_path())?;
+        }
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())
+    }
+
+    /// Returns up to `count` most recent entries, oldest first.
+    pub fn recent_entries(&self, count: usize) -> std::io::Result<Vec<AuditEntry>> {
+        let mut result = Vec::new();
+        for path in [self.rotated_path(), self.path.clone()] {
+            if path.exists() {
+                let reader = BufReader::new(std::fs::File::open(path)?);
+                for line in reader.lines() {
+                    result.extend(AuditEntry::from_line(&line?));
+                }
+            }
+        }
+
+        let skipped = result.len().saturating_sub(count);
+        Ok(result.split_off(skipped))
+    }
+}
+
+/// Starts recording mutating operations to the audit log in the save data directory,
+/// unless the configuration is locked.
+pub fn enable(max_size: Option<u64>) -> std::io::Result<()> {
+    // Otherwise a smaller size could be used to rotate away the recorded history
+    PathFilter::ensure_unlocked()?;
+    let path = PathFilter::save_data_directory()?.join(AUDIT_LOG_FILE_NAME);
+    let log = AuditLog::new(path, max_size.unwrap_or(DEFAULT_MAX_LOG_SIZE));
+    *AUDIT_LOG.lock().unwrap() = Some(log);
+
+    Ok(())
+}
+
+/// Stops recording operations, unless the configuration is locked.
+pub fn disable() -> std::io::Result<()> {
+    PathFilter::ensure_unlocked()?;
+    *AUDIT_LOG.lock().unwrap() = None;
+
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    AUDIT_LOG.lock().unwrap().is_some()
+}
+
+/// Returns up to `count` most recent entries from the audit log, oldest first.
+pub fn recent_entries(count: usize) -> std::io::Result<Vec<AuditEntry>> {
+    let path = PathFilter::save_data_directory()?.join(AUDIT_LOG_FILE_NAME);
+    match AUDIT_LOG.lock().unwrap().as_ref() {
+        Some(log) => log.recent_entries(count),
+        None => AuditLog::new(path, DEFAULT_MAX_LOG_SIZE).recent_entries(count)
+    }
+}
+
+/// Runs the given operation, and records its outcome in the audit log if it is enabled.
+pub(crate) fn track<T, F>(operation: &str, path: &Path, destination: Option<&Path>, bytes: Option<u64>, action: F) -> std::io::Result<T>
+    where F: FnOnce() -> std::io::Result<T>
+{
+    let result = action();
+
+    if let Some(log) = AUDIT_LOG.lock().unwrap().as_ref() {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let entry = AuditEntry {
+            timestamp,
+            operation: operation.to_string(),
+            path: normalize(path),
+            destination: destination.map(normalize),
+            bytes,
+            error: result.as_ref().err().map(|error| error.to_string()),
+        };
+
+        // Failing to write the audit log must not affect the operation itself
+        let _ = log.append(&entry);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::{AuditEntry, AuditLog};
+
+    fn entry(operation: &str, error: Option<&str>) -> AuditEntry {
+        AuditEntry {
+            timestamp: 1234,
+            operation: operation.to_string(),
+            path: "some/path.txt".to_string(),
+            destination: None,
+            bytes: Some(3),
+            error: error.map(|it| it.to_string()),
+        }
+    }
+
+    #[test]
+    fn entries_should_be_read_back_as_written() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(tmp_dir.path().join("audit.log"), 1024);
+        let mut failed = entry("copy", Some("Path is not\twithin an allowed directory"));
+        failed.destination = Some("other/path.txt".to_string());
+
+        log.append(&entry("write_string", None)).unwrap();
+        log.append(&failed).unwrap();
+        let result = log.recent_entries(10).unwrap();
+
+        failed.error = Some("Path is not within an allowed directory".to_string());
+        assert_eq!(vec![entry("write_string", None), failed], result);
+    }
+
+    #[test]
+    fn log_should_rotate_when_exceeding_max_size() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(tmp_dir.path().join("audit.log"), 64);
+
+        for _ in 0..5 {
+            log.append(&entry("append_string", None)).unwrap();
+        }
+
+        assert!(tmp_dir.path().join("audit.log.1").exists());
+        assert!(std::fs::metadata(tmp_dir.path().join("audit.log")).unwrap().len() <= 64);
+        assert_eq!(2, log.recent_entries(2).unwrap().len());
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::HashSet;
+use std::ffi::c_void;
+use std::io::{Error, ErrorKind};
+
+use mlua::prelude::{LuaError, LuaResult, LuaTable};
+
+/// Maximum nesting depth of values, like tables or negations, to fail on malicious input
+/// rather than overflow the stack.
+pub(crate) const MAX_DEPTH: usize = 200;
+
+pub(crate) fn conversion_error<S: Into<String>>(message: S) -> LuaError {
+    LuaError::external(Error::new(ErrorKind::InvalidInput, message.into()))
+}
+
+/// Keeps track of the tables being converted from Lua, to reject tables which contain themselves
+/// or are nested too deeply. Tables are only tracked while they're being converted, so the same
+/// table may appear multiple times, as long as it doesn't contain itself.
+pub(crate) struct TableTracker {
+    converting: HashSet<*const c_void>,
+    target: &'static str,
+}
+
+impl TableTracker {
+    /// Creates a tracker for conversions to the given target, like "JSON", used in error messages.
+    pub(crate) fn new(target: &'static str) -> TableTracker {
+        TableTracker { converting: HashSet::new(), target }
+    }
+
+    /// Converts the table with the given function, which is given this tracker back to convert
+    /// the table's values.
+    pub(crate) fn convert<'lua, T, F>(&mut self, table: LuaTable<'lua>, convert: F) -> LuaResult<T>
+    where
+        F: FnOnce(LuaTable<'lua>, &mut TableTracker) -> LuaResult<T>,
+    {
+        if self.converting.len() >= MAX_DEPTH {
+            return Err(conversion_error(format!("Cannot convert tables nested this deeply to {}", self.target)));
+        }
+        let pointer = table.to_pointer();
+        if !self.converting.insert(pointer) {
+            return Err(conversion_error(format!("Cannot convert a table which contains itself to {}", self.target)));
+        }
+
+        let result = convert(table, self);
+        self.converting.remove(&pointer);
+        result
+    }
+}
@@ -0,0 +1,212 @@
+use std::io::Error;
+use std::path::Path;
+use std::sync::RwLock;
+
+use globset::{GlobBuilder, GlobMatcher};
+use lazy_static::lazy_static;
+
+use crate::path_filter::{PathFilter, SandboxRoot};
+use crate::util::normalize;
+
+lazy_static! {
+    static ref DENY_LIST: RwLock<DenyList> = RwLock::new(DenyList::default());
+}
+
+/// Glob pattern for paths which must not be accessed, even though they are within a root.
+#[derive(Debug)]
+pub struct DenyRule {
+    /// Name of the root this rule applies to, or `None` if it applies to all roots
+    pub root_name: Option<String>,
+    pub pattern: String,
+    matcher: GlobMatcher,
+}
+
+impl DenyRule {
+    /// Patterns are matched case-insensitively against paths relative to the root, with `/`
+    /// as the separator. `*` doesn't match across directories, while `**` does.
+    pub fn new<S: AsRef<str>>(pattern: S, root_name: Option<String>) -> std::io::Result<DenyRule> {
+        let pattern = pattern.as_ref();
+        let glob = GlobBuilder::new(pattern)
+            .case_insensitive(true)
+            .literal_separator(true)
+            .build()
+            .map_err(|error| Error::other(format!("Invalid deny pattern '{}': {}", pattern, error)))?;
+
+        Ok(DenyRule {
+            root_name,
+            pattern: pattern.to_string(),
+            matcher: glob.compile_matcher(),
+        })
+    }
+
+    /// A rule matching a directory also matches everything inside of it.
+    fn matches(&self, root: &SandboxRoot, relative_path: &Path) -> bool {
+        if let Some(root_name) = &self.root_name {
+            if *root_name != root.name {
+                return false;
+            }
+        }
+
+        relative_path.ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| self.matcher.is_match(normalize(ancestor)))
+    }
+}
+
+/// Rules overriding the roots, which can be added to until the list is frozen.
+#[derive(Debug, Default)]
+pub struct DenyList {
+    rules: Vec<DenyRule>,
+    frozen: bool,
+}
+
+impl DenyList {
+    pub fn add(&mut self, rule: DenyRule) -> std::io::Result<()> {
+        if self.frozen {
+            Err(Error::other("Deny rules are frozen and can no longer be changed"))
+        } else {
+            self.rules.push(rule);
+            Ok(())
+        }
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    /// Returns the first rule denying access to the given path, which is within the given root.
+    pub fn denying_rule<P: AsRef<Path>>(&self, root: &SandboxRoot, path: P) -> std::io::Result<Option<&DenyRule>> {
+        let result = match root.relativize(path)? {
+            Some(relative_path) => self.rules.iter()
+                .find(|rule| rule.matches(root, &relative_path)),
+            None => None
+        };
+
+        Ok(result)
+    }
+}
+
+/// Adds a rule to the global deny list, unless it has been frozen or the configuration is locked.
+pub fn deny<S: AsRef<str>>(pattern: S, root_name: Option<String>) -> std::io::Result<()> {
+    PathFilter::ensure_unlocked()?;
+    let rule = DenyRule::new(pattern, root_name)?;
+    DENY_LIST.write().unwrap().add(rule)
+}
+
+/// Prevents any further changes to the global deny list.
+pub fn freeze() {
+    DENY_LIST.write().unwrap().freeze();
+}
+
+/// Returns the pattern of the first global rule denying access to the given path, which is
+/// within all of the given roots.
+pub(crate) fn denying_pattern<P: AsRef<Path>>(roots: &[SandboxRoot], path: P) -> std::io::Result<Option<String>> {
+    let deny_list = DENY_LIST.read().unwrap();
+    for root in roots {
+        if let Some(rule) = deny_list.denying_rule(root, &path)? {
+            return Ok(Some(rule.pattern.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deny_list::{DenyList, DenyRule};
+    use crate::path_filter::{PathFilter, Permission};
+    use crate::util::temp_root;
+
+    fn deny_list(patterns: &[&str]) -> DenyList {
+        let mut result = DenyList::default();
+        for pattern in patterns {
+            result.add(DenyRule::new(pattern, None).unwrap()).unwrap();
+        }
+        result
+    }
+
+    fn is_denied(deny_list: &DenyList, root_dir: &tempfile::TempDir, path: &str) -> bool {
+        let root = PathFilter::root_of(root_dir.path()).unwrap().unwrap();
+        deny_list.denying_rule(&root, root_dir.path().join(path)).unwrap().is_some()
+    }
+
+    #[test]
+    fn deny_rules_should_match_case_insensitively() {
+        let root_dir = temp_root(Permission::ReadOnly);
+        let deny_list = deny_list(&["resources/resource.dat", "Breach.exe"]);
+
+        assert!(is_denied(&deny_list, &root_dir, "resources/resource.dat"));
+        assert!(is_denied(&deny_list, &root_dir, "RESOURCES/Resource.DAT"));
+        assert!(is_denied(&deny_list, &root_dir, "breach.EXE"));
+        assert!(!is_denied(&deny_list, &root_dir, "resources/other.dat"));
+    }
+
+    #[test]
+    fn deny_rules_should_not_be_bypassed_with_parent_components() {
+        let root_dir = temp_root(Permission::ReadOnly);
+        std::fs::create_dir_all(root_dir.path().join("resources")).unwrap();
+        std::fs::create_dir_all(root_dir.path().join("scripts")).unwrap();
+        let deny_list = deny_list(&["resources/resource.dat"]);
+
+        assert!(is_denied(&deny_list, &root_dir, "scripts/../resources/resource.dat"));
+        assert!(is_denied(&deny_list, &root_dir, "./resources/./resource.dat"));
+        assert!(is_denied(&deny_list, &root_dir, "resources/../resources/resource.dat"));
+    }
+
+    #[test]
+    fn deny_rule_matching_directory_should_deny_its_contents() {
+        let root_dir = temp_root(Permission::ReadOnly);
+        let deny_list = deny_list(&["steam*", "*.dll"]);
+
+        assert!(is_denied(&deny_list, &root_dir, "steam_api.dll"));
+        assert!(is_denied(&deny_list, &root_dir, "SteamApps/common/file.txt"));
+        assert!(!is_denied(&deny_list, &root_dir, "mods/some_mod/lib.dll"));
+    }
+
+    #[test]
+    fn deny_rule_for_other_root_should_not_apply() {
+        let root_dir = temp_root(Permission::ReadOnly);
+        let mut deny_list = DenyList::default();
+        deny_list.add(DenyRule::new("*.dll", Some("some_other_root".to_string())).unwrap()).unwrap();
+
+        assert!(!is_denied(&deny_list, &root_dir, "steam_api.dll"));
+    }
+
+    #[test]
+    fn frozen_deny_list_should_reject_new_rules() {
+        let mut deny_list = deny_list(&["*.exe"]);
+
+        deny_list.freeze();
+        let result = deny_list.add(DenyRule::new("*.dll", None).unwrap());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn denied_path_should_not_be_accessible() {
+        let root_dir = temp_root(Permission::ReadWrite);
+        let root = PathFilter::root_of(root_dir.path()).unwrap().unwrap();
+        crate::deny_list::deny("secret.txt", Some(root.name)).unwrap();
+
+        assert!(!PathFilter::is_whitelisted(root_dir.path().join("SECRET.txt")).unwrap());
+        assert!(PathFilter::check_readable(root_dir.path().join("secret.txt")).is_err());
+        assert!(PathFilter::check_writable(root_dir.path().join("secret.txt")).is_err());
+        assert!(PathFilter::check_writable(root_dir.path().join("public.txt")).is_ok());
+    }
+
+    #[test]
+    fn rules_of_outer_root_should_apply_within_nested_root() {
+        let root_dir = temp_root(Permission::ReadOnly);
+        let root = PathFilter::root_of(root_dir.path()).unwrap().unwrap();
+        crate::deny_list::deny("resources/resource.dat", Some(root.name.clone())).unwrap();
+        std::fs::create_dir(root_dir.path().join("resources")).unwrap();
+        let nested_name = format!("{}_resources", root.name);
+        PathFilter::register_root(&nested_name, root_dir.path().join("resources"), Permission::ReadWrite).unwrap();
+
+        let denied_path = root_dir.path().join("resources/resource.dat");
+        assert_eq!(nested_name, PathFilter::root_of(&denied_path).unwrap().unwrap().name);
+        assert!(!PathFilter::is_whitelisted(&denied_path).unwrap());
+        assert!(PathFilter::check_writable(&denied_path).is_err());
+        assert!(PathFilter::check_writable(root_dir.path().join("resources/other.dat")).is_ok());
+    }
+}
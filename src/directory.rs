@@ -1,18 +1,40 @@
+use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 use walkdir::WalkDir;
 
+use crate::audit;
 use crate::file::File;
+use crate::hash::{hash_file, HashAlgorithm, Hasher};
+use crate::metadata;
+use crate::metadata::Metadata;
+use crate::path_compare::ComparisonMode;
 use crate::path_filter::PathFilter;
+use crate::quota;
+use crate::quota::Quota;
+use crate::scope::Scope;
 use crate::util::normalize;
+use crate::snapshot::{Snapshot, SnapshotEntry};
+use crate::sort::SortOrder;
+use crate::stats::{DirectoryStats, StatsCollector};
+use crate::transfer::{OverwritePolicy, TransferOptions};
+use crate::walk::{compile_glob, DirectoryEntry, WalkOptions};
+use crate::watcher::DirectoryWatcher;
 
 #[derive(Debug)]
 pub struct Directory {
     pub(crate) path: PathBuf,
+    pub(crate) scope: Option<Scope>,
 }
 
 impl Directory {
+    pub(crate) fn with_scope(mut self, scope: Option<Scope>) -> Directory {
+        self.scope = scope;
+        self
+    }
+
     pub fn path(&self) -> String {
         // Have directories report their path with a trailing slash, since that's sometimes
         // convenient when working with paths in Lua.
@@ -32,13 +54,13 @@ impl Directory {
 
     pub fn parent(&self) -> std::io::Result<Option<Directory>> {
         let maybe_dir = self.path.parent()
-            .map(|parent| Directory::from(parent));
+            .map(|parent| Directory::from(parent).with_scope(self.scope.clone()));
 
         if let Some(dir) = maybe_dir {
-            if PathFilter::is_whitelisted(&dir.path)? {
+            if self.is_whitelisted(&dir.path)? {
                 Ok(Option::Some(dir))
             } else {
-                Err(Error::new(ErrorKind::Other, "Parent is not an allowed directory"))
+                Err(Error::other("Parent is not an allowed directory"))
             }
         } else {
             Ok(None)
@@ -46,19 +68,22 @@ impl Directory {
     }
 
     pub fn root(&self) -> std::io::Result<Directory> {
-        let root_path = if self.path.starts_with(PathFilter::game_directory()?) {
-            PathFilter::game_directory()?
-        } else {
-            PathFilter::save_data_directory()?
+        let maybe_root = match &self.scope {
+            Some(scope) => scope.root_of(&self.path)?,
+            None => PathFilter::root_of(&self.path)?
+                .map(|root| Directory::from(root.path))
         };
 
-        Ok(Directory::from(root_path))
+        maybe_root.ok_or_else(|| Error::other("Path is not within an allowed directory"))
     }
 
+    #[allow(clippy::let_and_return)]
     pub fn relativize<P: AsRef<Path>>(&self, path: P) -> Option<String> {
+        // Checked on the given path, as the relative one would be resolved against the cwd
+        let is_dir = path.as_ref().is_dir();
         let normalized_path_relative_to_self = pathdiff::diff_paths(path, &self.path)
             .map(|path| {
-                if path.is_dir() {
+                if is_dir {
                     normalize(&path) + "/"
                 } else {
                     normalize(&path)
@@ -68,7 +93,29 @@ impl Directory {
         normalized_path_relative_to_self
     }
 
-    pub fn files(&self) -> std::io::Result<Vec<File>> {
+    /// Returns the file at the given path, relative to this directory.
+    pub fn file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<File> {
+        let path = PathFilter::absolutize(self.path.join(path))?;
+        if self.is_whitelisted(&path)? {
+            Ok(File::from(path).with_scope(self.scope.clone()))
+        } else {
+            Err(Error::other("Path points to a file not within an allowed directory"))
+        }
+    }
+
+    /// Returns the directory at the given path, relative to this directory.
+    pub fn directory<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Directory> {
+        let path = PathFilter::absolutize(self.path.join(path))?;
+        if self.is_whitelisted(&path)? {
+            Ok(Directory::from(path).with_scope(self.scope.clone()))
+        } else {
+            Err(Error::other("Path does not point to an allowed directory"))
+        }
+    }
+
+    /// Lists the files directly within this directory in the given order.
+    pub fn files(&self, order: SortOrder) -> std::io::Result<Vec<File>> {
+        self.check_readable(&self.path)?;
         if self.exists() {
             let mut result = Vec::new();
 
@@ -79,18 +126,21 @@ impl Directory {
                 .into_iter()
             {
                 let entry = entry?;
-                if entry.file_type().is_file() {
-                    result.push(File::from(entry.path()));
+                if entry.file_type().is_file() && self.is_whitelisted(entry.path())? {
+                    result.push(File::from(entry.path()).with_scope(self.scope.clone()));
                 }
             }
 
+            order.sort(&mut result, |entry| &entry.path);
             Ok(result)
         } else {
-            Err(Error::new(ErrorKind::Other, "Directory doesn't exist"))
+            Err(Error::other("Directory doesn't exist"))
         }
     }
 
-    pub fn directories(&self) -> std::io::Result<Vec<Directory>> {
+    /// Lists the directories directly within this directory in the given order.
+    pub fn directories(&self, order: SortOrder) -> std::io::Result<Vec<Directory>> {
+        self.check_readable(&self.path)?;
         if self.exists() {
             let mut result = Vec::new();
 
@@ -101,23 +151,302 @@ impl Directory {
                 .into_iter()
             {
                 let entry = entry?;
-                if entry.file_type().is_dir() {
-                    result.push(Directory::from(entry.path()));
+                if entry.file_type().is_dir() && self.is_whitelisted(entry.path())? {
+                    result.push(Directory::from(entry.path()).with_scope(self.scope.clone()));
                 }
             }
 
+            order.sort(&mut result, |entry| &entry.path);
             Ok(result)
         } else {
-            Err(Error::new(ErrorKind::Other, "Directory doesn't exist"))
+            Err(Error::other("Directory doesn't exist"))
         }
     }
 
-    pub fn make_directories(&self) -> std::io::Result<()> {
-        if PathFilter::is_whitelisted(&self.path)? {
-            std::fs::create_dir_all(&self.path)
+    /// Recursively lists the files and directories within this directory. Entries which
+    /// are not whitelisted are skipped along with their contents.
+    pub fn walk(&self, options: &WalkOptions) -> std::io::Result<Vec<DirectoryEntry>> {
+        self.check_readable(&self.path)?;
+        if !self.exists() {
+            return Err(Error::other("Directory doesn't exist"));
+        }
+
+        let mut walk_dir = WalkDir::new(&self.path)
+            .min_depth(1)
+            .follow_links(true);
+        if let Some(max_depth) = options.max_depth {
+            walk_dir = walk_dir.max_depth(max_depth);
+        }
+        // Values are cached, so that entries changing while being sorted can't make the
+        // order inconsistent
+        let order = options.order;
+        let values = Mutex::new(HashMap::new());
+        walk_dir = walk_dir.sort_by(move |a, b| {
+            let mut values = values.lock().unwrap();
+            let mut value = |path: &Path| values.entry(path.to_path_buf())
+                .or_insert_with(|| order.value(path))
+                .clone();
+            let (value_a, value_b) = (value(a.path()), value(b.path()));
+            order.compare_values(&value_a, &value_b)
+        });
+
+        let mut result = Vec::new();
+        for entry in walk_dir.into_iter().filter_entry(|entry| {
+            let is_excluded = options.exclude.as_ref()
+                .is_some_and(|exclude| exclude.is_match(self.entry_path(entry.path())));
+
+            !is_excluded && self.is_whitelisted(entry.path()).unwrap_or(false)
+        }) {
+            let entry = entry?;
+            let is_included = options.include.as_ref()
+                .is_none_or(|include| include.is_match(self.entry_path(entry.path())));
+            if !is_included {
+                continue;
+            }
+
+            if entry.file_type().is_file() && options.files {
+                result.push(DirectoryEntry::File(File::from(entry.path()).with_scope(self.scope.clone())));
+            } else if entry.file_type().is_dir() && options.directories {
+                result.push(DirectoryEntry::Directory(Directory::from(entry.path()).with_scope(self.scope.clone())));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the files and directories whose paths relative to this directory match the given
+    /// glob pattern, sorted by name. Supports `*`, `**`, `?`, character classes and `{a,b}`.
+    pub fn glob<S: AsRef<str>>(&self, pattern: S) -> std::io::Result<Vec<DirectoryEntry>> {
+        let components = pattern.as_ref().split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect::<Vec<_>>();
+        if components.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Glob pattern is empty"));
+        }
+
+        // Start walking from the deepest directory named literally in the pattern, and don't
+        // descend deeper than the pattern can match, to avoid visiting unrelated entries
+        let literal_count = components.iter()
+            .take_while(|component| !component.contains(['*', '?', '[', '{', '\\']))
+            .count()
+            .min(components.len() - 1);
+        let base = self.directory(components[..literal_count].join("/"))?;
+        if !base.exists() {
+            return Ok(Vec::new());
+        }
+
+        let remainder = components[literal_count..].join("/");
+        let max_depth = if remainder.contains("**") {
+            None
         } else {
-            Err(Error::new(ErrorKind::Other, "Path does not point to an allowed directory"))
+            Some(components.len() - literal_count)
+        };
+
+        base.walk(&WalkOptions {
+            max_depth,
+            include: Some(compile_glob(remainder)?),
+            order: SortOrder::default(),
+            ..WalkOptions::default()
+        })
+    }
+
+    /// Recursively copies the contents of this directory into the given destination directory,
+    /// returning the number of files copied. Like moves, this is refused if the directory contains
+    /// symbolic links.
+    pub fn copy_to<P: AsRef<Path>>(&self, destination: P, options: &mut TransferOptions) -> std::io::Result<usize> {
+        let destination = PathFilter::absolutize(destination)?;
+        audit::track("copy_directory", &self.path, Some(&destination), None, || {
+            self.transfer(&destination, options, false)
+        })
+    }
+
+    /// Recursively moves the contents of this directory into the given destination directory,
+    /// returning the number of files moved. Source directories are removed once they are empty,
+    /// so any with skipped contents remain. Files are moved one by one, copying them if they're
+    /// on another volume, so if moving one fails, the files moved before it stay moved, and the
+    /// error says how many there were. Directories containing symbolic links are refused, as moving
+    /// the contents of a linked directory would empty the link's target instead.
+    pub fn move_to<P: AsRef<Path>>(&self, destination: P, options: &mut TransferOptions) -> std::io::Result<usize> {
+        let destination = PathFilter::absolutize(destination)?;
+        audit::track("move_directory", &self.path, Some(&destination), None, || {
+            self.transfer(&destination, options, true)
+        })
+    }
+
+    fn transfer(&self, destination: &Path, options: &mut TransferOptions, remove_source: bool) -> std::io::Result<usize> {
+        self.check_readable(&self.path)?;
+        if remove_source {
+            self.check_writable(&self.path)?;
+            if self.is_root(&self.path)? {
+                return Err(Error::new(ErrorKind::PermissionDenied, "Root directories cannot be moved"));
+            }
+        }
+        self.check_writable(destination)?;
+        if !self.exists() {
+            return Err(Error::other("Directory doesn't exist"));
+        }
+        if ComparisonMode::native().starts_with(PathFilter::resolve(destination)?, PathFilter::resolve(&self.path)?) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Directory cannot be transferred into itself"));
+        }
+
+        let mut files = Vec::new();
+        let mut directories = Vec::new();
+        for entry in self.walk(&WalkOptions {
+            exclude: options.skip.clone(),
+            order: SortOrder::default(),
+            ..WalkOptions::default()
+        })? {
+            match entry {
+                DirectoryEntry::File(file) => files.push(file),
+                DirectoryEntry::Directory(directory) => directories.push(directory),
+            }
+        }
+        let entry_paths = files.iter().map(|file| &file.path).chain(directories.iter().map(|directory| &directory.path));
+        for path in std::iter::once(&self.path).chain(entry_paths) {
+            if std::fs::symlink_metadata(path)?.file_type().is_symlink() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Directories containing symbolic links cannot be transferred: {}", normalize(path))
+                ));
+            }
+        }
+        let target = |path: &Path| destination.join(path.strip_prefix(&self.path).unwrap());
+
+        // Check for conflicts up front, so that nothing is transferred if there are any
+        if options.overwrite == OverwritePolicy::Fail {
+            if let Some(file) = files.iter().find(|file| target(&file.path).exists()) {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("Destination file already exists: {}", normalize(target(&file.path)))
+                ));
+            }
         }
+
+        Directory::from(destination).with_scope(self.scope.clone()).make_directories()?;
+        for directory in &directories {
+            Directory::from(target(&directory.path)).with_scope(self.scope.clone()).make_directories()?;
+        }
+
+        let mut transferred = 0;
+        for (index, file) in files.iter().enumerate() {
+            let file_destination = target(&file.path);
+            if options.overwrite != OverwritePolicy::Skip || !file_destination.exists() {
+                if remove_source {
+                    file.move_file(&file_destination).map_err(|error| Error::new(
+                        error.kind(),
+                        format!("Moving '{}' failed after {} files were moved: {}", self.entry_path(&file.path), transferred, error)
+                    ))?;
+                } else {
+                    file.copy(&file_destination)?;
+                }
+                transferred += 1;
+            }
+
+            if let Some(progress) = options.progress.as_mut() {
+                progress(&self.entry_path(&file.path), index + 1, files.len())?;
+            }
+        }
+
+        if remove_source {
+            // Deepest directories come last in the listing, so removing in reverse
+            // empties children before their parents
+            let emptied_directories = directories.iter()
+                .rev()
+                .map(|directory| directory.path.as_path())
+                .chain(std::iter::once(self.path.as_path()));
+            for directory in emptied_directories {
+                if std::fs::read_dir(directory)?.next().is_none() {
+                    std::fs::remove_dir(directory)?;
+                }
+            }
+        }
+
+        Ok(transferred)
+    }
+
+    /// Gathers statistics about the contents of this directory in a single walk, descending
+    /// at most `max_depth` levels if specified.
+    pub fn stats(&self, max_depth: Option<usize>) -> std::io::Result<DirectoryStats> {
+        let mut collector = StatsCollector::default();
+        for entry in self.walk(&WalkOptions { max_depth, ..WalkOptions::default() })? {
+            match entry {
+                DirectoryEntry::File(file) => {
+                    let size = std::fs::metadata(&file.path)?.len();
+                    collector.add_file(self.entry_path(&file.path), file.extension(), size);
+                }
+                DirectoryEntry::Directory(_) => collector.add_directory(),
+            }
+        }
+
+        Ok(collector.finish())
+    }
+
+    /// Records the paths, sizes and modification times of the contents of this directory,
+    /// to be compared against later.
+    pub fn snapshot(&self) -> std::io::Result<Snapshot> {
+        let mut result = Snapshot::default();
+        for entry in self.walk(&WalkOptions::default())? {
+            let (path, is_directory) = match &entry {
+                DirectoryEntry::File(file) => (&file.path, false),
+                DirectoryEntry::Directory(directory) => (&directory.path, true),
+            };
+            let metadata = Metadata::of(path)?;
+
+            result.insert(self.entry_path(path), SnapshotEntry {
+                is_directory,
+                size: metadata.size,
+                modified: metadata.modified,
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Starts watching this directory for changes, including those in its subdirectories
+    /// if `recursive` is set.
+    pub fn watch(&self, recursive: bool) -> std::io::Result<DirectoryWatcher> {
+        self.check_readable(&self.path)?;
+        DirectoryWatcher::new(&self.path, recursive, self.scope.clone())
+    }
+
+    /// Computes a digest of the relative paths and contents of all files within this directory,
+    /// which doesn't depend on the order the filesystem lists them in.
+    pub fn hash_tree(&self, algorithm: HashAlgorithm) -> std::io::Result<String> {
+        self.check_readable(&self.path)?;
+        if !self.exists() {
+            return Err(Error::other("Directory doesn't exist"));
+        }
+
+        let mut files = Vec::new();
+        for entry in WalkDir::new(&self.path)
+            .follow_links(true)
+            .into_iter()
+            .filter_entry(|entry| self.is_whitelisted(entry.path()).unwrap_or(false))
+        {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                let relative_path = self.entry_path(entry.path());
+                files.push((relative_path, entry.into_path()));
+            }
+        }
+        files.sort();
+
+        let mut hasher = Hasher::new(algorithm);
+        for (relative_path, path) in files {
+            hasher.update(relative_path.as_bytes());
+            hasher.update(b"\0");
+            hasher.update(hash_file(path, algorithm)?.as_bytes());
+            hasher.update(b"\n");
+        }
+
+        Ok(hasher.finalize())
+    }
+
+    pub fn make_directories(&self) -> std::io::Result<()> {
+        audit::track("make_directories", &self.path, None, None, || {
+            self.check_writable(&self.path)?;
+            std::fs::create_dir_all(&self.path)
+        })
     }
 
     pub fn exists(&self) -> bool {
@@ -125,19 +454,93 @@ impl Directory {
     }
 
     pub fn is_ancestor<P: AsRef<Path>>(&self, path: P) -> std::io::Result<bool> {
+        self.is_ancestor_in_mode(path, ComparisonMode::native())
+    }
+
+    fn is_ancestor_in_mode<P: AsRef<Path>>(&self, path: P, mode: ComparisonMode) -> std::io::Result<bool> {
         let path = path.as_ref();
         if path.is_absolute() {
-            Ok(path.starts_with(&self.path))
+            Ok(mode.starts_with(path, &self.path))
         } else {
-            Err(Error::new(ErrorKind::Other, "Not an absolute path"))
+            Err(Error::other("Not an absolute path"))
         }
     }
 
     pub fn delete(&self) -> std::io::Result<()> {
-        if self.exists() {
-            std::fs::remove_dir_all(&self.path)
-        } else {
+        audit::track("delete_directory", &self.path, None, None, || {
+            self.check_writable(&self.path)?;
+            if self.is_root(&self.path)? {
+                return Err(Error::new(ErrorKind::PermissionDenied, "Root directories cannot be deleted"));
+            }
+
+            if self.exists() {
+                // Every entry has to be writable, so that denied entries can't be removed along
+                // with their parent. Symbolic links are checked like any other entry, so links
+                // leading outside of the roots prevent the deletion too.
+                for entry in WalkDir::new(&self.path).min_depth(1) {
+                    self.check_writable(entry?.path())?;
+                }
+
+                let size = Quota::measure(&self.path)? as i64;
+                std::fs::remove_dir_all(&self.path)?;
+                quota::reserve(&self.path, self.scope.as_ref(), -size)?.commit();
+            }
+
             Ok(())
+        })
+    }
+
+    pub fn metadata(&self) -> std::io::Result<Metadata> {
+        self.check_readable(&self.path)?;
+        Metadata::of(&self.path)
+    }
+
+    /// Sets the modification time, in seconds since the Unix epoch.
+    pub fn set_modified(&self, timestamp: u64) -> std::io::Result<()> {
+        audit::track("set_modified", &self.path, None, None, || {
+            self.check_writable(&self.path)?;
+            metadata::set_modified(&self.path, timestamp)
+        })
+    }
+
+    pub fn set_read_only(&self, read_only: bool) -> std::io::Result<()> {
+        audit::track("set_read_only", &self.path, None, None, || {
+            self.check_writable(&self.path)?;
+            metadata::set_read_only(&self.path, read_only)
+        })
+    }
+
+    /// Returns the path of an entry found within this directory, relative to it.
+    fn entry_path(&self, path: &Path) -> String {
+        normalize(path.strip_prefix(&self.path).unwrap_or(path))
+    }
+
+    /// Returns true if the given path is a root, or one of the directories of this directory's scope.
+    fn is_root<P: AsRef<Path>>(&self, path: P) -> std::io::Result<bool> {
+        match &self.scope {
+            Some(scope) => Ok(scope.is_root(&path)? || PathFilter::is_root(&path)?),
+            None => PathFilter::is_root(path)
+        }
+    }
+
+    fn is_whitelisted<P: AsRef<Path>>(&self, path: P) -> std::io::Result<bool> {
+        match &self.scope {
+            Some(scope) => scope.is_whitelisted(path),
+            None => PathFilter::is_whitelisted(path)
+        }
+    }
+
+    fn check_readable<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        match &self.scope {
+            Some(scope) => scope.check_readable(path),
+            None => PathFilter::check_readable(path)
+        }
+    }
+
+    fn check_writable<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        match &self.scope {
+            Some(scope) => scope.check_writable(path),
+            None => PathFilter::check_writable(path)
         }
     }
 }
@@ -145,7 +548,8 @@ impl Directory {
 impl<P: AsRef<Path>> From<P> for Directory where PathBuf: From<P> {
     fn from(path: P) -> Self {
         Directory {
-            path: PathBuf::from(path)
+            path: PathBuf::from(path),
+            scope: None,
         }
     }
 }
@@ -153,7 +557,13 @@ impl<P: AsRef<Path>> From<P> for Directory where PathBuf: From<P> {
 #[cfg(test)]
 mod tests {
     use crate::directory::Directory;
-    use crate::path_filter::PathFilter;
+    use crate::hash::HashAlgorithm;
+    use crate::path_compare::ComparisonMode;
+    use crate::path_filter::{PathFilter, Permission};
+    use crate::util::temp_root;
+    use crate::sort::{SortDirection, SortKey, SortOrder};
+    use crate::transfer::{OverwritePolicy, TransferOptions};
+    use crate::walk::{compile_glob, DirectoryEntry, WalkOptions};
 
     #[test]
     fn path_should_be_reported_with_trailing_slash() {
@@ -188,4 +598,319 @@ mod tests {
         assert!(result.is_ok());
         assert!(result.unwrap());
     }
-}
+
+    #[test]
+    fn is_ancestor_should_ignore_case_when_case_insensitive() {
+        let dir = Directory::from(PathFilter::game_directory().unwrap().join("Some/Path"));
+        let test_path = PathFilter::game_directory().unwrap().join("some/path/test");
+
+        assert!(dir.is_ancestor_in_mode(&test_path, ComparisonMode::CaseInsensitive).unwrap());
+        assert!(!dir.is_ancestor_in_mode(&test_path, ComparisonMode::CaseSensitive).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listings_should_skip_symlinks_escaping_allowed_directories() {
+        let sandbox = temp_root(Permission::ReadOnly);
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        std::fs::write(sandbox.path().join("file.txt"), "file").unwrap();
+        std::os::unix::fs::symlink(outside.path(), sandbox.path().join("outside_dir")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret.txt"), sandbox.path().join("outside_file.txt")).unwrap();
+
+        let dir = Directory::from(sandbox.path());
+        let files: Vec<String> = dir.files(SortOrder::default()).unwrap().iter().map(|it| it.name()).collect();
+        let directories = dir.directories(SortOrder::default()).unwrap();
+
+        assert_eq!(vec!["file.txt"], files);
+        assert!(directories.is_empty());
+    }
+
+    #[test]
+    fn mutating_operations_should_be_refused_outside_of_roots() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::create_dir(outside.path().join("existing")).unwrap();
+        let dir = Directory::from(outside.path().join("existing"));
+        let new_dir = Directory::from(outside.path().join("new"));
+
+        assert!(dir.delete().is_err());
+        assert!(new_dir.make_directories().is_err());
+        assert!(dir.exists());
+        assert!(!new_dir.exists());
+    }
+
+    #[test]
+    fn root_directory_should_not_be_deletable() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let dir = Directory::from(tmp_dir.path());
+
+        assert!(dir.delete().is_err());
+        assert!(dir.exists());
+    }
+
+    #[test]
+    fn directory_containing_denied_entries_should_not_be_deletable() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let root = PathFilter::root_of(tmp_dir.path()).unwrap().unwrap();
+        crate::deny_list::deny("**/protected.txt", Some(root.name)).unwrap();
+        std::fs::create_dir_all(tmp_dir.path().join("mod/nested")).unwrap();
+        std::fs::write(tmp_dir.path().join("mod/nested/protected.txt"), "qwe").unwrap();
+        std::fs::create_dir_all(tmp_dir.path().join("other")).unwrap();
+        std::fs::write(tmp_dir.path().join("other/public.txt"), "qwe").unwrap();
+
+        assert!(Directory::from(tmp_dir.path().join("mod")).delete().is_err());
+        assert!(tmp_dir.path().join("mod/nested/protected.txt").exists());
+        Directory::from(tmp_dir.path().join("other")).delete().unwrap();
+        assert!(!tmp_dir.path().join("other").exists());
+    }
+
+    #[test]
+    fn tree_hash_should_depend_on_paths_and_contents() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        std::fs::create_dir(tmp_dir.path().join("nested")).unwrap();
+        std::fs::write(tmp_dir.path().join("a.txt"), "qwe").unwrap();
+        std::fs::write(tmp_dir.path().join("nested/b.txt"), "asd").unwrap();
+        let directory = Directory::from(tmp_dir.path());
+
+        let original = directory.hash_tree(HashAlgorithm::Sha256).unwrap();
+        assert_eq!(original, directory.hash_tree(HashAlgorithm::Sha256).unwrap());
+
+        std::fs::rename(tmp_dir.path().join("nested/b.txt"), tmp_dir.path().join("nested/c.txt")).unwrap();
+        let renamed = directory.hash_tree(HashAlgorithm::Sha256).unwrap();
+        assert_ne!(original, renamed);
+
+        std::fs::write(tmp_dir.path().join("a.txt"), "qwf").unwrap();
+        assert_ne!(renamed, directory.hash_tree(HashAlgorithm::Sha256).unwrap());
+    }
+
+    fn walked_paths(directory: &Directory, options: &WalkOptions) -> Vec<String> {
+        relative_paths(directory, directory.walk(options).unwrap())
+    }
+
+    fn relative_paths(directory: &Directory, entries: Vec<DirectoryEntry>) -> Vec<String> {
+        entries.iter()
+            .map(|entry| match entry {
+                DirectoryEntry::File(file) => directory.entry_path(&file.path),
+                DirectoryEntry::Directory(child) => directory.entry_path(&child.path) + "/",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn walk_should_respect_depth_and_filters() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        std::fs::create_dir_all(tmp_dir.path().join("mods/a/img")).unwrap();
+        std::fs::create_dir_all(tmp_dir.path().join("mods/b")).unwrap();
+        std::fs::write(tmp_dir.path().join("init.lua"), "").unwrap();
+        std::fs::write(tmp_dir.path().join("mods/a/init.lua"), "").unwrap();
+        std::fs::write(tmp_dir.path().join("mods/a/img/unit.png"), "").unwrap();
+        std::fs::write(tmp_dir.path().join("mods/b/init.lua"), "").unwrap();
+        let directory = Directory::from(tmp_dir.path());
+        let options = |options: WalkOptions| WalkOptions { order: SortOrder::default(), ..options };
+
+        assert_eq!(
+            vec!["init.lua", "mods/", "mods/a/", "mods/a/img/", "mods/a/img/unit.png", "mods/a/init.lua", "mods/b/", "mods/b/init.lua"],
+            walked_paths(&directory, &options(WalkOptions::default()))
+        );
+        assert_eq!(
+            vec!["init.lua", "mods/"],
+            walked_paths(&directory, &options(WalkOptions { max_depth: Some(1), ..WalkOptions::default() }))
+        );
+        assert_eq!(
+            vec!["init.lua", "mods/b/init.lua"],
+            walked_paths(&directory, &options(WalkOptions {
+                include: Some(compile_glob("**/*.lua").unwrap()),
+                exclude: Some(compile_glob("mods/a").unwrap()),
+                ..WalkOptions::default()
+            }))
+        );
+        assert_eq!(
+            vec!["mods/", "mods/a/", "mods/a/img/", "mods/b/"],
+            walked_paths(&directory, &options(WalkOptions { files: false, ..WalkOptions::default() }))
+        );
+    }
+
+    #[test]
+    fn walk_should_be_sorted_by_name_by_default() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        for name in ["c.lua", "B.lua", "a.lua"] {
+            std::fs::write(tmp_dir.path().join(name), "").unwrap();
+        }
+        let directory = Directory::from(tmp_dir.path());
+
+        assert_eq!(vec!["a.lua", "B.lua", "c.lua"], walked_paths(&directory, &WalkOptions::default()));
+    }
+
+    #[test]
+    fn glob_should_match_relative_to_directory() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        std::fs::create_dir_all(tmp_dir.path().join("img/units/mech")).unwrap();
+        std::fs::write(tmp_dir.path().join("img/units/a.png"), "").unwrap();
+        std::fs::write(tmp_dir.path().join("img/units/b.jpg"), "").unwrap();
+        std::fs::write(tmp_dir.path().join("img/units/c.txt"), "").unwrap();
+        std::fs::write(tmp_dir.path().join("img/units/mech/d.png"), "").unwrap();
+        let directory = Directory::from(tmp_dir.path());
+        let glob = |pattern: &str| relative_paths(&directory, directory.glob(pattern).unwrap());
+
+        assert_eq!(vec!["img/units/a.png"], glob("img/units/*.png"));
+        assert_eq!(vec!["img/units/a.png", "img/units/mech/d.png"], glob("img/**/*.png"));
+        assert_eq!(vec!["img/units/a.png", "img/units/b.jpg"], glob("img/units/?.{png,jpg}"));
+        assert_eq!(vec!["img/units/c.txt", "img/units/mech/"], glob("img/units/[c-m]*"));
+        assert!(glob("sounds/*.wav").is_empty());
+        assert!(directory.glob("../*").is_err());
+    }
+
+    #[test]
+    fn listings_should_be_sorted_in_requested_order() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        std::fs::write(tmp_dir.path().join("mod10.lua"), "q").unwrap();
+        std::fs::write(tmp_dir.path().join("mod2.lua"), "qwe").unwrap();
+        std::fs::write(tmp_dir.path().join("mod1.lua"), "qw").unwrap();
+        std::fs::create_dir(tmp_dir.path().join("mod10")).unwrap();
+        std::fs::create_dir(tmp_dir.path().join("mod2")).unwrap();
+        let directory = Directory::from(tmp_dir.path());
+        let file_names = |order: SortOrder| -> Vec<String> {
+            directory.files(order).unwrap().iter().map(|file| file.name()).collect()
+        };
+
+        assert_eq!(vec!["mod1.lua", "mod10.lua", "mod2.lua"], file_names(SortOrder::default()));
+        assert_eq!(
+            vec!["mod1.lua", "mod2.lua", "mod10.lua"],
+            file_names(SortOrder::new(SortKey::Natural, SortDirection::Ascending))
+        );
+        assert_eq!(
+            vec!["mod2.lua", "mod1.lua", "mod10.lua"],
+            file_names(SortOrder::new(SortKey::Size, SortDirection::Descending))
+        );
+
+        let directories = directory.directories(SortOrder::new(SortKey::Natural, SortDirection::Ascending)).unwrap();
+        assert_eq!(vec!["mod2", "mod10"], directories.iter().map(|dir| dir.name()).collect::<Vec<_>>());
+    }
+
+    fn mod_directory(root: &tempfile::TempDir) -> Directory {
+        std::fs::create_dir_all(root.path().join("mod/scripts")).unwrap();
+        std::fs::write(root.path().join("mod/init.lua"), "init").unwrap();
+        std::fs::write(root.path().join("mod/scripts/a.lua"), "a").unwrap();
+        std::fs::write(root.path().join("mod/scripts/a.lua.bak"), "backup").unwrap();
+
+        Directory::from(root.path().join("mod"))
+    }
+
+    #[test]
+    fn copy_should_transfer_contents_except_skipped() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let source = mod_directory(&tmp_dir);
+        let mut progress = Vec::new();
+
+        let copied = source.copy_to(tmp_dir.path().join("backup"), &mut TransferOptions {
+            skip: Some(compile_glob("**/*.bak").unwrap()),
+            progress: Some(Box::new(|path, done, total| {
+                progress.push(format!("{} {}/{}", path, done, total));
+                Ok(())
+            })),
+            ..TransferOptions::default()
+        }).unwrap();
+
+        assert_eq!(2, copied);
+        assert_eq!(vec!["init.lua 1/2", "scripts/a.lua 2/2"], progress);
+        assert_eq!("a", std::fs::read_to_string(tmp_dir.path().join("backup/scripts/a.lua")).unwrap());
+        assert!(!tmp_dir.path().join("backup/scripts/a.lua.bak").exists());
+        assert!(tmp_dir.path().join("mod/scripts/a.lua").exists());
+    }
+
+    #[test]
+    fn copy_should_follow_overwrite_policy() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let source = mod_directory(&tmp_dir);
+        std::fs::create_dir(tmp_dir.path().join("backup")).unwrap();
+        std::fs::write(tmp_dir.path().join("backup/init.lua"), "existing").unwrap();
+        let destination = tmp_dir.path().join("backup");
+        let policy = |overwrite| TransferOptions { overwrite, ..TransferOptions::default() };
+
+        assert!(source.copy_to(&destination, &mut policy(OverwritePolicy::Fail)).is_err());
+        assert!(!destination.join("scripts").exists());
+
+        assert_eq!(2, source.copy_to(&destination, &mut policy(OverwritePolicy::Skip)).unwrap());
+        assert_eq!("existing", std::fs::read_to_string(destination.join("init.lua")).unwrap());
+
+        assert_eq!(3, source.copy_to(&destination, &mut policy(OverwritePolicy::Overwrite)).unwrap());
+        assert_eq!("init", std::fs::read_to_string(destination.join("init.lua")).unwrap());
+    }
+
+    #[test]
+    fn move_should_remove_emptied_source_directories() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let source = mod_directory(&tmp_dir);
+
+        let moved = source.move_to(tmp_dir.path().join("moved"), &mut TransferOptions::default()).unwrap();
+
+        assert_eq!(3, moved);
+        assert!(!tmp_dir.path().join("mod").exists());
+        assert_eq!("init", std::fs::read_to_string(tmp_dir.path().join("moved/init.lua")).unwrap());
+    }
+
+    #[test]
+    fn transfer_should_be_refused_outside_of_roots_and_into_itself() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let outside = tempfile::tempdir().unwrap();
+        let source = mod_directory(&tmp_dir);
+
+        assert!(source.copy_to(outside.path(), &mut TransferOptions::default()).is_err());
+        assert!(source.copy_to(tmp_dir.path().join("mod/nested"), &mut TransferOptions::default()).is_err());
+        assert!(source.move_to(outside.path(), &mut TransferOptions::default()).is_err());
+        assert_eq!(0, std::fs::read_dir(outside.path()).unwrap().count());
+        assert!(Directory::from(tmp_dir.path()).move_to(outside.path(), &mut TransferOptions::default()).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn transfer_should_be_refused_for_directories_containing_symlinks() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let source = mod_directory(&tmp_dir);
+        std::fs::create_dir(tmp_dir.path().join("shared")).unwrap();
+        std::fs::write(tmp_dir.path().join("shared/lib.lua"), "lib").unwrap();
+        std::os::unix::fs::symlink(tmp_dir.path().join("shared"), tmp_dir.path().join("mod/shared")).unwrap();
+
+        assert!(source.move_to(tmp_dir.path().join("moved"), &mut TransferOptions::default()).is_err());
+        assert!(source.copy_to(tmp_dir.path().join("copied"), &mut TransferOptions::default()).is_err());
+        assert!(tmp_dir.path().join("shared/lib.lua").exists());
+        assert!(tmp_dir.path().join("mod/init.lua").exists());
+        assert!(!tmp_dir.path().join("moved").exists());
+        assert!(!tmp_dir.path().join("copied").exists());
+    }
+
+    #[test]
+    fn stats_should_respect_depth_limit() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        let directory = mod_directory(&tmp_dir);
+
+        let stats = directory.stats(None).unwrap();
+        assert_eq!(11, stats.total_bytes);
+        assert_eq!(3, stats.file_count);
+        assert_eq!(1, stats.directory_count);
+        assert_eq!(("scripts/a.lua.bak".to_string(), 6), stats.largest_files[0]);
+        assert_eq!(5, stats.extensions["lua"].bytes);
+
+        let shallow_stats = directory.stats(Some(1)).unwrap();
+        assert_eq!(4, shallow_stats.total_bytes);
+        assert_eq!(1, shallow_stats.file_count);
+    }
+
+    #[test]
+    fn snapshots_should_detect_changes_between_them() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let directory = mod_directory(&tmp_dir);
+        let older = directory.snapshot().unwrap();
+
+        std::fs::write(tmp_dir.path().join("mod/init.lua"), "changed").unwrap();
+        std::fs::remove_file(tmp_dir.path().join("mod/scripts/a.lua.bak")).unwrap();
+        std::fs::write(tmp_dir.path().join("mod/scripts/b.lua"), "b").unwrap();
+        let diff = older.diff(&directory.snapshot().unwrap());
+
+        assert_eq!(4, older.entries().len());
+        assert!(older.entries()["scripts"].is_directory);
+        assert_eq!(vec!["scripts/b.lua"], diff.added);
+        assert_eq!(vec!["scripts/a.lua.bak"], diff.removed);
+        assert_eq!(vec!["init.lua"], diff.changed);
+    }
+}
\ No newline at end of file
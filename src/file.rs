@@ -2,16 +2,34 @@ use std::fs::OpenOptions;
 use std::io::{Error, ErrorKind, Write};
 use std::path::{Path, PathBuf};
 
+use crate::audit;
 use crate::directory::Directory;
+use crate::file_handle::{FileHandle, Lines, OpenMode};
+use crate::hash;
+use crate::hash::HashAlgorithm;
+use crate::json;
+use crate::lua_data;
+use crate::lua_data::{LuaData, SerializeOptions};
+use crate::metadata;
+use crate::metadata::Metadata;
 use crate::path_filter::PathFilter;
+use crate::quota;
+use crate::quota::Reservation;
+use crate::scope::Scope;
 use crate::util::normalize;
 
 #[derive(Debug)]
 pub struct File {
     pub(crate) path: PathBuf,
+    pub(crate) scope: Option<Scope>,
 }
 
 impl File {
+    pub(crate) fn with_scope(mut self, scope: Option<Scope>) -> File {
+        self.scope = scope;
+        self
+    }
+
     pub fn path(&self) -> String {
         normalize(&self.path)
     }
@@ -38,13 +56,13 @@ impl File {
 
     pub fn parent(&self) -> std::io::Result<Option<Directory>> {
         let maybe_dir = self.path.parent()
-            .map(|parent| Directory::from(parent));
+            .map(|parent| Directory::from(parent).with_scope(self.scope.clone()));
 
         if let Some(dir) = maybe_dir {
-            if PathFilter::is_whitelisted(&dir.path)? {
+            if self.is_whitelisted(&dir.path)? {
                 Ok(Option::Some(dir))
             } else {
-                Err(Error::new(ErrorKind::Other, "Parent is not an allowed directory"))
+                Err(Error::other("Parent is not an allowed directory"))
             }
         } else {
             Ok(None)
@@ -52,84 +70,148 @@ impl File {
     }
 
     pub fn root(&self) -> std::io::Result<Directory> {
-        let root_path = if self.path.starts_with(PathFilter::game_directory()?) {
-            PathFilter::game_directory()?
-        } else {
-            PathFilter::save_data_directory()?
+        let maybe_root = match &self.scope {
+            Some(scope) => scope.root_of(&self.path)?,
+            None => PathFilter::root_of(&self.path)?
+                .map(|root| Directory::from(root.path))
         };
 
-        Ok(Directory::from(root_path))
+        maybe_root.ok_or_else(|| Error::other("Path is not within an allowed directory"))
     }
 
     pub fn read_to_byte_array(&self) -> std::io::Result<Vec<u8>> {
+        self.check_readable(&self.path)?;
         if self.exists() {
             std::fs::read(&self.path)
         } else {
-            Err(Error::new(ErrorKind::Other, "File doesn't exist"))
+            Err(Error::other("File doesn't exist"))
         }
     }
 
     pub fn read_to_string(&self) -> std::io::Result<String> {
+        self.check_readable(&self.path)?;
         if self.exists() {
             std::fs::read_to_string(&self.path)
         } else {
-            Err(Error::new(ErrorKind::Other, "File doesn't exist"))
+            Err(Error::other("File doesn't exist"))
         }
     }
 
     pub fn write_string<S: AsRef<str> + AsRef<[u8]>>(&self, content: S) -> std::io::Result<()> {
-        let maybe_parent = &self.path.parent();
-        if let Some(parent) = maybe_parent {
-            std::fs::create_dir_all(parent)?;
-        }
-        std::fs::write(&self.path, content)
+        let content: &[u8] = content.as_ref();
+        audit::track("write_string", &self.path, None, Some(content.len() as u64), || {
+            self.check_writable(&self.path)?;
+            let reservation = self.reserve(&self.path, content.len() as i64 - quota::file_size(&self.path))?;
+
+            let maybe_parent = &self.path.parent();
+            if let Some(parent) = maybe_parent {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_atomically(&self.path, |file| file.write_all(content))?;
+
+            reservation.commit();
+            Ok(())
+        })
+    }
+
+    pub fn read_json(&self) -> std::io::Result<serde_json::Value> {
+        json::parse(self.read_to_string()?)
+    }
+
+    /// Writes the value as JSON, indented if `pretty` is set.
+    pub fn write_json(&self, value: &serde_json::Value, pretty: bool) -> std::io::Result<()> {
+        self.write_string(json::stringify(value, pretty)?)
+    }
+
+    /// Parses the file as Lua data, like the game's save files, without executing it.
+    pub fn read_lua_data(&self) -> std::io::Result<LuaData> {
+        lua_data::parse(self.read_to_byte_array()?)
+    }
+
+    /// Writes the data as a Lua data file, assigned to the given name, or returned if no name
+    /// is given.
+    pub fn write_lua_data(&self, name: Option<&str>, data: &LuaData, options: &SerializeOptions) -> std::io::Result<()> {
+        self.write_string(lua_data::serialize(name, data, options)?)
     }
 
     pub fn append_string<S: AsRef<str>>(&self, content: S) -> std::io::Result<()> {
-        let maybe_parent = &self.path.parent();
-        if let Some(parent) = maybe_parent {
-            std::fs::create_dir_all(parent)?;
-        }
-        let mut file = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&self.path)
-            .unwrap();
+        let content = content.as_ref().as_bytes();
+        audit::track("append_string", &self.path, None, Some(content.len() as u64), || {
+            self.check_writable(&self.path)?;
+            let reservation = self.reserve(&self.path, content.len() as i64)?;
 
-        file.write(content.as_ref().as_bytes())
-            .map(|_| ())
+            let maybe_parent = &self.path.parent();
+            if let Some(parent) = maybe_parent {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&self.path)?;
+            file.write_all(content)?;
+
+            reservation.commit();
+            Ok(())
+        })
     }
 
     pub fn write_byte_array(&self, content: Vec<u8>) -> std::io::Result<()> {
-        let maybe_parent = &self.path.parent();
-        if let Some(parent) = maybe_parent {
-            std::fs::create_dir_all(parent)?;
-        }
-        std::fs::write(&self.path, content)
+        audit::track("write_byte_array", &self.path, None, Some(content.len() as u64), || {
+            self.check_writable(&self.path)?;
+            let reservation = self.reserve(&self.path, content.len() as i64 - quota::file_size(&self.path))?;
+
+            let maybe_parent = &self.path.parent();
+            if let Some(parent) = maybe_parent {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_atomically(&self.path, |file| file.write_all(&content))?;
+
+            reservation.commit();
+            Ok(())
+        })
     }
 
     pub fn copy<P: AsRef<Path>>(&self, destination: &P) -> std::io::Result<()> {
-        if PathFilter::is_whitelisted(destination)? {
+        audit::track("copy", &self.path, Some(destination.as_ref()), None, || {
+            self.check_readable(&self.path)?;
+            self.check_writable(destination)?;
+            let reservation = self.reserve(destination, quota::file_size(&self.path) - quota::file_size(destination))?;
+
             let maybe_dest_parent = destination.as_ref().parent();
             if let Some(dest_parent) = maybe_dest_parent {
                 std::fs::create_dir_all(dest_parent)?;
             }
-            std::fs::copy(&self.path, destination).map(|_| ())
-        } else {
-            Err(Error::new(ErrorKind::Other, "Destination is not within allowed directory"))
-        }
+            std::fs::copy(&self.path, destination)?;
+
+            reservation.commit();
+            Ok(())
+        })
     }
 
     pub fn move_file<P: AsRef<Path>>(&self, destination: &P) -> std::io::Result<()> {
-        if PathFilter::is_whitelisted(destination)? {
+        audit::track("move", &self.path, Some(destination.as_ref()), None, || {
+            // Moving removes the source file, so it needs to be writable as well
+            self.check_writable(&self.path)?;
+            self.check_writable(destination)?;
+            let size = quota::file_size(&self.path);
+            let replaced = quota::file_size(destination);
+            let reservation = quota::reserve_move(&self.path, destination, self.scope.as_ref(), size, replaced)?;
+
             let maybe_dest_parent = destination.as_ref().parent();
             if let Some(dest_parent) = maybe_dest_parent {
                 std::fs::create_dir_all(dest_parent)?;
             }
-            std::fs::rename(&self.path, destination)
-        } else {
-            Err(Error::new(ErrorKind::Other, "Destination is not within allowed directory"))
-        }
+            match std::fs::rename(&self.path, destination) {
+                // Files can't be renamed across volumes, like from a Steam library to the documents
+                Err(error) if error.kind() == ErrorKind::CrossesDevices => {
+                    move_across_devices(&self.path, destination.as_ref())?
+                }
+                result => result?,
+            }
+
+            reservation.commit();
+            Ok(())
+        })
     }
 
     pub fn exists(&self) -> bool {
@@ -137,29 +219,160 @@ impl File {
     }
 
     pub fn delete(&self) -> std::io::Result<()> {
-        if self.exists() {
-            std::fs::remove_file(&self.path)
-        } else {
+        audit::track("delete", &self.path, None, None, || {
+            self.check_writable(&self.path)?;
+            if self.exists() {
+                let size = quota::file_size(&self.path);
+                std::fs::remove_file(&self.path)?;
+                self.reserve(&self.path, -size)?.commit();
+            }
+
             Ok(())
+        })
+    }
+
+    pub fn metadata(&self) -> std::io::Result<Metadata> {
+        self.check_readable(&self.path)?;
+        Metadata::of(&self.path)
+    }
+
+    /// Sets the modification time, in seconds since the Unix epoch.
+    pub fn set_modified(&self, timestamp: u64) -> std::io::Result<()> {
+        audit::track("set_modified", &self.path, None, None, || {
+            self.check_writable(&self.path)?;
+            metadata::set_modified(&self.path, timestamp)
+        })
+    }
+
+    pub fn set_read_only(&self, read_only: bool) -> std::io::Result<()> {
+        audit::track("set_read_only", &self.path, None, None, || {
+            self.check_writable(&self.path)?;
+            metadata::set_read_only(&self.path, read_only)
+        })
+    }
+
+    /// Hashes the content of the file, returning the digest as a hexadecimal string.
+    pub fn hash(&self, algorithm: HashAlgorithm) -> std::io::Result<String> {
+        self.check_readable(&self.path)?;
+        hash::hash_file(&self.path, algorithm)
+    }
+
+    /// Opens the file for incremental reading and/or writing, depending on `mode`.
+    /// Access is checked once, when the file is opened.
+    pub fn open(&self, mode: OpenMode) -> std::io::Result<FileHandle> {
+        if mode.is_writable() {
+            self.check_writable(&self.path)?;
+        } else {
+            self.check_readable(&self.path)?;
+        }
+
+        FileHandle::open(&self.path, mode, self.scope.clone())
+    }
+
+    /// Returns an iterator over the lines of the file, reading it incrementally.
+    pub fn lines(&self) -> std::io::Result<Lines> {
+        self.check_readable(&self.path)?;
+        Lines::open(&self.path)
+    }
+
+    fn is_whitelisted<P: AsRef<Path>>(&self, path: P) -> std::io::Result<bool> {
+        match &self.scope {
+            Some(scope) => scope.is_whitelisted(path),
+            None => PathFilter::is_whitelisted(path)
         }
     }
+
+    fn check_readable<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        match &self.scope {
+            Some(scope) => scope.check_readable(path),
+            None => PathFilter::check_readable(path)
+        }
+    }
+
+    fn check_writable<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        match &self.scope {
+            Some(scope) => scope.check_writable(path),
+            None => PathFilter::check_writable(path)
+        }
+    }
+
+    fn reserve<P: AsRef<Path>>(&self, path: P, growth: i64) -> std::io::Result<Reservation> {
+        quota::reserve(path, self.scope.as_ref(), growth)
+    }
 }
 
 impl<P: AsRef<Path>> From<P> for File where PathBuf: From<P> {
     fn from(path: P) -> Self {
         File {
-            path: PathBuf::from(path)
+            path: PathBuf::from(path),
+            scope: None,
         }
     }
 }
 
+/// Writes a file by writing to a temporary sibling file first and then replacing the target
+/// with it, so that the target is left intact if writing is interrupted. Symbolic links are
+/// written through, and the permissions of an existing target are kept.
+fn write_atomically<F>(path: &Path, write: F) -> std::io::Result<()>
+    where F: FnOnce(&mut std::fs::File) -> std::io::Result<()>
+{
+    let target = PathFilter::resolve(path)?;
+    let existing_permissions = match std::fs::metadata(&target) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(error) if error.kind() == ErrorKind::NotFound => None,
+        Err(error) => return Err(error),
+    };
+    // Replacing the file would succeed on some platforms, but not on others
+    if existing_permissions.as_ref().is_some_and(|permissions| permissions.readonly()) {
+        return Err(Error::new(ErrorKind::PermissionDenied, "File is read-only"));
+    }
+
+    let parent = target.parent()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Path has no parent directory"))?;
+    // Created with a unique name, so that no existing file is overwritten, and deleted when
+    // dropped if anything fails
+    let mut temp_file = tempfile::NamedTempFile::new_in(parent)?;
+    write(temp_file.as_file_mut())?;
+    if let Some(permissions) = existing_permissions {
+        temp_file.as_file().set_permissions(permissions)?;
+    }
+    temp_file.as_file().sync_all()?;
+    temp_file.persist(&target).map_err(|error| error.error)?;
+
+    // Makes the rename itself durable
+    #[cfg(unix)]
+    std::fs::File::open(parent)?.sync_all()?;
+
+    Ok(())
+}
+
+/// Moves a file by copying it next to the destination and then replacing the destination with
+/// the copy, before deleting the source. If the source can't be deleted, both files remain.
+fn move_across_devices(source: &Path, destination: &Path) -> std::io::Result<()> {
+    let parent = destination.parent()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Path has no parent directory"))?;
+    let mut temp_file = tempfile::NamedTempFile::new_in(parent)?;
+    std::io::copy(&mut std::fs::File::open(source)?, temp_file.as_file_mut())?;
+    temp_file.as_file().set_permissions(std::fs::metadata(source)?.permissions())?;
+    temp_file.as_file().sync_all()?;
+    temp_file.persist(destination).map_err(|error| error.error)?;
+
+    std::fs::remove_file(source)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::file::File;
+    use std::io::{Error, Write};
+
+    use crate::file::{File, move_across_devices, write_atomically};
+    use crate::path_filter::{PathFilter, Permission};
+    use crate::quota;
+    use crate::util::temp_root;
 
     #[test]
     fn append_should_create_if_file_does_not_exist() {
-        let tmp_file = tempfile::NamedTempFile::new().unwrap();
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let tmp_file = tempfile::NamedTempFile::new_in(tmp_dir.path()).unwrap();
         let tmp_path = tmp_file.into_temp_path();
 
         let file = File::from(tmp_path.to_path_buf());
@@ -169,4 +382,231 @@ mod tests {
         let result = file.read_to_string().unwrap();
         assert_eq!(result, "qweasd");
     }
+
+    #[test]
+    fn write_should_be_refused_within_read_only_root() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        let file = File::from(tmp_dir.path().join("file.txt"));
+
+        let result = file.write_string("qwe");
+
+        assert!(result.is_err());
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn copy_should_be_allowed_from_read_only_to_read_write_root() {
+        let source_dir = temp_root(Permission::ReadOnly);
+        let destination_dir = temp_root(Permission::ReadWrite);
+        std::fs::write(source_dir.path().join("file.txt"), "qwe").unwrap();
+        let file = File::from(source_dir.path().join("file.txt"));
+
+        file.copy(&destination_dir.path().join("copy.txt")).unwrap();
+
+        assert_eq!("qwe", std::fs::read_to_string(destination_dir.path().join("copy.txt")).unwrap());
+    }
+
+    #[test]
+    fn move_should_be_refused_out_of_read_only_root() {
+        let source_dir = temp_root(Permission::ReadOnly);
+        let destination_dir = temp_root(Permission::ReadWrite);
+        std::fs::write(source_dir.path().join("file.txt"), "qwe").unwrap();
+        let file = File::from(source_dir.path().join("file.txt"));
+
+        let result = file.move_file(&destination_dir.path().join("moved.txt"));
+
+        assert!(result.is_err());
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn mutating_operations_should_be_refused_outside_of_roots() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("existing.txt"), "qwe").unwrap();
+        let file = File::from(outside.path().join("existing.txt"));
+        let new_file = File::from(outside.path().join("new.txt"));
+
+        assert!(new_file.write_string("asd").is_err());
+        assert!(new_file.append_string("asd").is_err());
+        assert!(new_file.write_byte_array(vec![1, 2, 3]).is_err());
+        assert!(file.copy(&outside.path().join("copy.txt")).is_err());
+        assert!(file.move_file(&outside.path().join("moved.txt")).is_err());
+        assert!(file.delete().is_err());
+
+        assert!(!new_file.exists());
+        assert!(!outside.path().join("copy.txt").exists());
+        assert!(!outside.path().join("moved.txt").exists());
+        assert_eq!("qwe", std::fs::read_to_string(outside.path().join("existing.txt")).unwrap());
+    }
+
+    #[test]
+    fn write_to_system_path_should_be_refused() {
+        let file = File::from("/etc/x");
+
+        let result = file.write_string("qwe");
+
+        assert_eq!(std::io::ErrorKind::PermissionDenied, result.unwrap_err().kind());
+    }
+
+    #[test]
+    fn copy_out_of_roots_should_be_refused() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(tmp_dir.path().join("file.txt"), "qwe").unwrap();
+        let file = File::from(tmp_dir.path().join("file.txt"));
+
+        assert!(file.copy(&outside.path().join("copy.txt")).is_err());
+        assert!(file.move_file(&outside.path().join("moved.txt")).is_err());
+        assert!(file.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_through_symlink_escaping_root_should_be_refused() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), tmp_dir.path().join("link")).unwrap();
+        let file = File::from(tmp_dir.path().join("link/file.txt"));
+
+        assert!(file.write_string("qwe").is_err());
+        assert!(!outside.path().join("file.txt").exists());
+    }
+
+    #[test]
+    fn writes_beyond_root_quota_should_be_refused() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let root_name = PathFilter::root_of(tmp_dir.path()).unwrap().unwrap().name;
+        quota::set_root_quota(&root_name, 10).unwrap();
+        let file = File::from(tmp_dir.path().join("file.txt"));
+
+        file.write_string("123456").unwrap();
+        assert!(file.copy(&tmp_dir.path().join("copy.txt")).is_err());
+        assert!(file.write_byte_array(vec![0; 11]).is_err());
+        // Overwriting only counts the difference in size
+        file.write_string("1234567890").unwrap();
+
+        assert_eq!(0, quota::root_quota(&root_name).unwrap().remaining());
+        assert!(!tmp_dir.path().join("copy.txt").exists());
+    }
+
+    #[test]
+    fn move_within_root_quota_should_only_count_the_difference() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let root_name = PathFilter::root_of(tmp_dir.path()).unwrap().unwrap().name;
+        quota::set_root_quota(&root_name, 10).unwrap();
+        let file = File::from(tmp_dir.path().join("file.txt"));
+        file.write_string("123456").unwrap();
+
+        file.move_file(&tmp_dir.path().join("nested/moved.txt")).unwrap();
+
+        assert_eq!(4, quota::root_quota(&root_name).unwrap().remaining());
+        assert!(tmp_dir.path().join("nested/moved.txt").exists());
+    }
+
+    #[test]
+    fn interrupted_write_should_leave_original_intact() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let file = File::from(tmp_dir.path().join("config.lua"));
+        file.write_string("original").unwrap();
+
+        let result = write_atomically(&file.path, |file| {
+            file.write_all(b"partial")?;
+            Err(Error::other("interrupted"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!("original", file.read_to_string().unwrap());
+        assert_eq!(1, std::fs::read_dir(tmp_dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn write_should_replace_content_without_leaving_temp_file() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let file = File::from(tmp_dir.path().join("config.lua"));
+
+        file.write_string("original").unwrap();
+        file.write_byte_array(b"new".to_vec()).unwrap();
+
+        assert_eq!("new", file.read_to_string().unwrap());
+        assert_eq!(1, std::fs::read_dir(tmp_dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn move_across_devices_should_replace_destination_and_remove_source() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let destination_dir = tempfile::tempdir().unwrap();
+        std::fs::write(source_dir.path().join("file.txt"), "new").unwrap();
+        std::fs::write(destination_dir.path().join("file.txt"), "old").unwrap();
+
+        move_across_devices(&source_dir.path().join("file.txt"), &destination_dir.path().join("file.txt")).unwrap();
+
+        assert!(!source_dir.path().join("file.txt").exists());
+        assert_eq!("new", std::fs::read_to_string(destination_dir.path().join("file.txt")).unwrap());
+        assert_eq!(1, std::fs::read_dir(destination_dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn write_should_be_refused_for_read_only_file() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let file = File::from(tmp_dir.path().join("config.lua"));
+        file.write_string("original").unwrap();
+        file.set_read_only(true).unwrap();
+
+        let result = file.write_string("new");
+        file.set_read_only(false).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!("original", file.read_to_string().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn group_writable_file_set_read_only_should_refuse_writes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let file = File::from(tmp_dir.path().join("config.lua"));
+        file.write_string("original").unwrap();
+        std::fs::set_permissions(&file.path, std::fs::Permissions::from_mode(0o664)).unwrap();
+
+        file.set_read_only(true).unwrap();
+        let read_only = file.metadata().unwrap().read_only;
+        let result = file.write_string("new");
+        file.set_read_only(false).unwrap();
+
+        assert!(read_only);
+        assert!(result.is_err());
+        assert_eq!("original", file.read_to_string().unwrap());
+        assert_eq!(0o644, std::fs::metadata(&file.path).unwrap().permissions().mode() & 0o777);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_should_keep_symlinks_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let target = tmp_dir.path().join("target.lua");
+        std::fs::write(&target, "original").unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink(&target, tmp_dir.path().join("link.lua")).unwrap();
+
+        File::from(tmp_dir.path().join("link.lua")).write_string("new").unwrap();
+
+        assert!(std::fs::symlink_metadata(tmp_dir.path().join("link.lua")).unwrap().file_type().is_symlink());
+        assert_eq!("new", std::fs::read_to_string(&target).unwrap());
+        assert_eq!(0o640, std::fs::metadata(&target).unwrap().permissions().mode() & 0o777);
+    }
+
+    #[test]
+    fn metadata_setters_should_respect_read_only_roots() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        std::fs::write(tmp_dir.path().join("file.txt"), "qwe").unwrap();
+        let file = File::from(tmp_dir.path().join("file.txt"));
+
+        assert_eq!(3, file.metadata().unwrap().size);
+        assert!(file.set_modified(0).is_err());
+        assert!(file.set_read_only(true).is_err());
+        assert!(!file.metadata().unwrap().read_only);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,351 @@
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::audit;
+use crate::quota;
+use crate::scope::Scope;
+
+/// Modes a file can be opened in, mirroring the ones of Lua's `io.open`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenMode {
+    /// `r`: reading, from the start of an existing file
+    Read,
+    /// `w`: writing, truncating the file or creating it
+    Write,
+    /// `a`: writing at the end of the file, creating it if needed
+    Append,
+    /// `r+`: reading and writing, from the start of an existing file
+    ReadUpdate,
+    /// `w+`: reading and writing, truncating the file or creating it
+    WriteUpdate,
+    /// `a+`: reading anywhere and writing at the end of the file, creating it if needed
+    AppendUpdate,
+}
+
+impl OpenMode {
+    pub fn is_readable(&self) -> bool {
+        !matches!(self, OpenMode::Write | OpenMode::Append)
+    }
+
+    pub fn is_writable(&self) -> bool {
+        *self != OpenMode::Read
+    }
+
+    fn is_truncating(&self) -> bool {
+        matches!(self, OpenMode::Write | OpenMode::WriteUpdate)
+    }
+
+    fn is_appending(&self) -> bool {
+        matches!(self, OpenMode::Append | OpenMode::AppendUpdate)
+    }
+
+    fn options(&self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options.read(self.is_readable())
+            .write(self.is_writable() && !self.is_appending())
+            .append(self.is_appending())
+            .truncate(self.is_truncating())
+            .create(self.is_truncating() || self.is_appending());
+        options
+    }
+}
+
+impl FromStr for OpenMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Binary flag is accepted for compatibility with Lua, but it makes no difference
+        match s.replace('b', "").as_str() {
+            "r" => Ok(OpenMode::Read),
+            "w" => Ok(OpenMode::Write),
+            "a" => Ok(OpenMode::Append),
+            "r+" => Ok(OpenMode::ReadUpdate),
+            "w+" => Ok(OpenMode::WriteUpdate),
+            "a+" => Ok(OpenMode::AppendUpdate),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Invalid file mode '{}'", s)))
+        }
+    }
+}
+
+/// Origin for seeking within a file handle, mirroring the ones of Lua's `file:seek`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Whence {
+    Set,
+    Current,
+    End,
+}
+
+impl FromStr for Whence {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "set" => Ok(Whence::Set),
+            "cur" => Ok(Whence::Current),
+            "end" => Ok(Whence::End),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Invalid seek origin '{}'", s)))
+        }
+    }
+}
+
+/// An open file, allowing it to be read and written incrementally. Access is checked once,
+/// when the file is opened. The file is closed when the handle is dropped.
+#[derive(Debug)]
+pub struct FileHandle {
+    path: PathBuf,
+    mode: OpenMode,
+    scope: Option<Scope>,
+    inner: Option<BufReader<std::fs::File>>,
+    bytes_written: u64,
+}
+
+impl FileHandle {
+    /// Opens the file at the given path. Callers are responsible for checking access beforehand.
+    pub(crate) fn open<P: AsRef<Path>>(path: P, mode: OpenMode, scope: Option<Scope>) -> std::io::Result<FileHandle> {
+        let path = path.as_ref();
+        if mode.is_truncating() || mode.is_appending() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let truncated_size = if mode.is_truncating() { quota::file_size(path) } else { 0 };
+        let file = mode.options().open(path)?;
+        if truncated_size > 0 {
+            quota::reserve(path, scope.as_ref(), -truncated_size)?.commit();
+        }
+
+        Ok(FileHandle {
+            path: path.to_path_buf(),
+            mode,
+            scope,
+            inner: Some(BufReader::new(file)),
+            bytes_written: 0,
+        })
+    }
+
+    fn inner(&mut self) -> std::io::Result<&mut BufReader<std::fs::File>> {
+        self.inner.as_mut()
+            .ok_or_else(|| Error::other("File handle is closed"))
+    }
+
+    fn ensure_readable(&self) -> std::io::Result<()> {
+        if self.mode.is_readable() {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::PermissionDenied, "File handle is not open for reading"))
+        }
+    }
+
+    /// Reads up to `count` bytes, or everything up to the end of the file if `count` is `None`.
+    /// Returns `None` when the end of the file has already been reached.
+    pub fn read(&mut self, count: Option<u64>) -> std::io::Result<Option<Vec<u8>>> {
+        self.ensure_readable()?;
+        let reader = self.inner()?;
+        if reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        let mut result = Vec::new();
+        match count {
+            Some(count) => reader.take(count).read_to_end(&mut result)?,
+            None => reader.read_to_end(&mut result)?
+        };
+
+        Ok(Some(result))
+    }
+
+    /// Reads a single line, without its line terminator (`\n` or `\r\n`).
+    /// Returns `None` when the end of the file has already been reached.
+    pub fn read_line(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        self.ensure_readable()?;
+        read_line(self.inner()?)
+    }
+
+    pub fn write(&mut self, content: &[u8]) -> std::io::Result<()> {
+        if !self.mode.is_writable() {
+            return Err(Error::new(ErrorKind::PermissionDenied, "File handle is not open for writing"));
+        }
+
+        let mode = self.mode;
+        let reader = self.inner()?;
+        // Seeking discards anything read ahead, so that the write happens at the logical position
+        // rather than at the end of the read buffer
+        let position = reader.stream_position()?;
+        reader.seek(SeekFrom::Start(position))?;
+        let size = reader.get_ref().metadata()?.len();
+        let growth = if mode.is_appending() {
+            content.len() as u64
+        } else {
+            (position + content.len() as u64).saturating_sub(size)
+        };
+
+        let reservation = quota::reserve(&self.path, self.scope.as_ref(), growth as i64)?;
+        self.inner()?.get_mut().write_all(content)?;
+        reservation.commit();
+
+        self.bytes_written += content.len() as u64;
+        Ok(())
+    }
+
+    /// Moves to the given offset from `whence`, returning the resulting position from the start.
+    pub fn seek(&mut self, whence: Whence, offset: i64) -> std::io::Result<u64> {
+        let position = match whence {
+            Whence::Set => SeekFrom::Start(offset.max(0) as u64),
+            Whence::Current => SeekFrom::Current(offset),
+            Whence::End => SeekFrom::End(offset),
+        };
+
+        self.inner()?.seek(position)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.inner()?.get_mut().flush()
+    }
+
+    /// Closes the handle. Closing an already closed handle does nothing.
+    pub fn close(&mut self) -> std::io::Result<()> {
+        if let Some(mut reader) = self.inner.take() {
+            if self.mode.is_writable() {
+                let bytes_written = self.bytes_written;
+                audit::track("write_handle", &self.path, None, Some(bytes_written), || {
+                    reader.get_mut().flush()
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_none()
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// Iterator over the lines of a file, without their line terminators, reading it incrementally.
+#[derive(Debug)]
+pub struct Lines {
+    reader: BufReader<std::fs::File>,
+}
+
+impl Lines {
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Lines> {
+        Ok(Lines {
+            reader: BufReader::new(std::fs::File::open(path)?),
+        })
+    }
+}
+
+impl Iterator for Lines {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        read_line(&mut self.reader).transpose()
+    }
+}
+
+/// Reads a single line, without its line terminator (`\n` or `\r\n`).
+/// Returns `None` when the end of the input has already been reached.
+pub(crate) fn read_line<R: BufRead>(reader: &mut R) -> std::io::Result<Option<Vec<u8>>> {
+    let mut result = Vec::new();
+    if reader.read_until(b'\n', &mut result)? == 0 {
+        return Ok(None);
+    }
+
+    if result.last() == Some(&b'\n') {
+        result.pop();
+        if result.last() == Some(&b'\r') {
+            result.pop();
+        }
+    }
+
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file::File;
+    use crate::file_handle::{OpenMode, Whence};
+    use crate::path_filter::Permission;
+    use crate::util::temp_root;
+
+    #[test]
+    fn handle_should_read_lines_and_chunks() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        std::fs::write(tmp_dir.path().join("file.txt"), "first\r\nsecond\nthird").unwrap();
+        let mut handle = File::from(tmp_dir.path().join("file.txt")).open(OpenMode::Read).unwrap();
+
+        assert_eq!(Some(b"first".to_vec()), handle.read_line().unwrap());
+        assert_eq!(Some(b"sec".to_vec()), handle.read(Some(3)).unwrap());
+        assert_eq!(Some(b"ond".to_vec()), handle.read_line().unwrap());
+        assert_eq!(Some(b"third".to_vec()), handle.read_line().unwrap());
+        assert_eq!(None, handle.read_line().unwrap());
+        assert_eq!(None, handle.read(None).unwrap());
+    }
+
+    #[test]
+    fn handle_should_write_at_sought_position() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let file = File::from(tmp_dir.path().join("file.txt"));
+        let mut handle = file.open(OpenMode::WriteUpdate).unwrap();
+
+        handle.write(b"0123456789").unwrap();
+        assert_eq!(2, handle.seek(Whence::Set, 2).unwrap());
+        assert_eq!(Some(b"23".to_vec()), handle.read(Some(2)).unwrap());
+        handle.write(b"ab").unwrap();
+        assert_eq!(10, handle.seek(Whence::End, 0).unwrap());
+        handle.close().unwrap();
+
+        assert!(handle.is_closed());
+        assert!(handle.write(b"qwe").is_err());
+        assert_eq!("0123ab6789", file.read_to_string().unwrap());
+    }
+
+    #[test]
+    fn opening_for_writing_should_respect_permissions() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        std::fs::write(tmp_dir.path().join("file.txt"), "qwe").unwrap();
+        let file = File::from(tmp_dir.path().join("file.txt"));
+
+        assert!(file.open(OpenMode::Read).is_ok());
+        assert!(file.open(OpenMode::Write).is_err());
+        assert!(file.open(OpenMode::Append).is_err());
+        assert_eq!("qwe", file.read_to_string().unwrap());
+    }
+
+    #[test]
+    fn read_only_handle_should_refuse_writes() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        std::fs::write(tmp_dir.path().join("file.txt"), "qwe").unwrap();
+        let mut handle = File::from(tmp_dir.path().join("file.txt")).open(OpenMode::Read).unwrap();
+
+        assert!(handle.write(b"asd").is_err());
+    }
+
+    #[test]
+    fn lines_should_strip_both_line_terminators() {
+        let tmp_dir = temp_root(Permission::ReadOnly);
+        std::fs::write(tmp_dir.path().join("file.txt"), "first\r\n\nthird\n").unwrap();
+
+        let lines = File::from(tmp_dir.path().join("file.txt")).lines().unwrap()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(vec![b"first".to_vec(), b"".to_vec(), b"third".to_vec()], lines);
+    }
+
+    #[test]
+    fn open_modes_should_parse_like_lua() {
+        assert_eq!(OpenMode::Read, "rb".parse().unwrap());
+        assert_eq!(OpenMode::AppendUpdate, "a+".parse().unwrap());
+        assert!("x".parse::<OpenMode>().is_err());
+    }
+}
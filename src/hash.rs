@@ -0,0 +1,118 @@
+use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// Size of the chunks files are read in while hashing them.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Crc32,
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "").as_str() {
+            "crc32" => Ok(HashAlgorithm::Crc32),
+            "md5" => Ok(HashAlgorithm::Md5),
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Unsupported hash algorithm '{}'", s)))
+        }
+    }
+}
+
+/// Incremental hash computation, using any of the supported algorithms.
+pub(crate) enum Hasher {
+    Crc32(crc32fast::Hasher),
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Hasher {
+        match algorithm {
+            HashAlgorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(hasher) => hasher.update(data),
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Returns the digest as a lowercase hexadecimal string.
+    pub(crate) fn finalize(self) -> String {
+        let digest = match self {
+            Hasher::Crc32(hasher) => hasher.finalize().to_be_bytes().to_vec(),
+            Hasher::Md5(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        };
+
+        digest.iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// Hashes the content of the file at the given path, without loading it into memory all at once.
+pub(crate) fn hash_file<P: AsRef<Path>>(path: P, algorithm: HashAlgorithm) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0; CHUNK_SIZE];
+
+    loop {
+        let count = file.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::{hash_file, HashAlgorithm};
+
+    #[test]
+    fn file_hashes_should_match_known_digests() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("file.txt");
+        std::fs::write(&path, "The quick brown fox jumps over the lazy dog").unwrap();
+
+        assert_eq!("414fa339", hash_file(&path, HashAlgorithm::Crc32).unwrap());
+        assert_eq!("9e107d9d372bb6826bd81d3542a419d6", hash_file(&path, HashAlgorithm::Md5).unwrap());
+        assert_eq!("2fd4e1c67a2d28fced849ee1bb76e7391b93eb12", hash_file(&path, HashAlgorithm::Sha1).unwrap());
+        assert_eq!(
+            "d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592",
+            hash_file(&path, HashAlgorithm::Sha256).unwrap()
+        );
+    }
+
+    #[test]
+    fn algorithm_names_should_be_lenient() {
+        assert_eq!(HashAlgorithm::Sha256, "SHA-256".parse().unwrap());
+        assert_eq!(HashAlgorithm::Crc32, "crc32".parse().unwrap());
+        assert!("sha512".parse::<HashAlgorithm>().is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use std::io::{Error, ErrorKind};
+
+use mlua::{Integer, LightUserData, Lua, Value as LuaValue};
+use mlua::prelude::{LuaResult, LuaTable};
+use serde_json::{Map, Number, Value};
+
+use crate::conversion::{conversion_error, TableTracker};
+
+/// Parses JSON text. Errors report the line and column the problem was found at.
+pub fn parse<S: AsRef<str>>(text: S) -> std::io::Result<Value> {
+    serde_json::from_str(text.as_ref())
+        .map_err(|error| Error::new(ErrorKind::InvalidData, format!("Invalid JSON: {}", error)))
+}
+
+/// Formats a value as JSON, either compactly or indented with two spaces.
+pub fn stringify(value: &Value, pretty: bool) -> std::io::Result<String> {
+    let result = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+
+    result.map_err(Error::other)
+}
+
+/// Value standing in for JSON's null in Lua, where `nil` can't be stored in tables.
+pub(crate) fn null<'lua>() -> LuaValue<'lua> {
+    LuaValue::LightUserData(LightUserData(std::ptr::null_mut()))
+}
+
+/// Converts JSON into Lua values. Arrays become tables with keys from 1 to n, and nulls
+/// become the `null` sentinel, so that they're preserved within tables.
+pub(crate) fn to_lua(lua: &Lua, value: Value) -> LuaResult<LuaValue<'_>> {
+    let result = match value {
+        Value::Null => null(),
+        Value::Bool(value) => LuaValue::Boolean(value),
+        // Lua's integers are only 32 bits wide on 32-bit targets, so larger ones become numbers
+        Value::Number(number) => match number.as_i64().and_then(|integer| Integer::try_from(integer).ok()) {
+            Some(integer) => LuaValue::Integer(integer),
+            None => LuaValue::Number(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(string) => LuaValue::String(lua.create_string(&string)?),
+        Value::Array(values) => {
+            let table = lua.create_table_with_capacity(values.len() as i32, 0)?;
+            for value in values {
+                table.raw_push(to_lua(lua, value)?)?;
+            }
+            LuaValue::Table(table)
+        }
+        Value::Object(entries) => {
+            let table = lua.create_table_with_capacity(0, entries.len() as i32)?;
+            for (key, value) in entries {
+                table.raw_set(key, to_lua(lua, value)?)?;
+            }
+            LuaValue::Table(table)
+        }
+    };
+
+    Ok(result)
+}
+
+/// Converts Lua values into JSON. Tables whose keys are exactly the integers from 1 to n
+/// become arrays, and all other tables become objects, including empty ones. Both `nil`
+/// and the `null` sentinel become null.
+pub(crate) fn from_lua(value: LuaValue, sort_keys: bool) -> LuaResult<Value> {
+    from_lua_value(value, sort_keys, &mut TableTracker::new("JSON"))
+}
+
+fn from_lua_value(value: LuaValue, sort_keys: bool, tables: &mut TableTracker) -> LuaResult<Value> {
+    let result = match value {
+        LuaValue::Nil => Value::Null,
+        LuaValue::LightUserData(data) if data.0.is_null() => Value::Null,
+        LuaValue::Boolean(value) => Value::Bool(value),
+        LuaValue::Integer(integer) => Value::from(integer),
+        LuaValue::Number(number) => number_to_json(number)?,
+        LuaValue::String(string) => Value::String(string.to_str()?.to_string()),
+        LuaValue::Table(table) => tables.convert(table, |table, tables| table_to_json(table, sort_keys, tables))?,
+        other => return Err(conversion_error(format!("Cannot convert a value of type '{}' to JSON", other.type_name())))
+    };
+
+    Ok(result)
+}
+
+fn number_to_json(number: f64) -> LuaResult<Value> {
+    // Lua 5.1 doesn't distinguish integers, so write whole numbers without a fraction
+    if number.fract() == 0.0 && number.abs() < (1u64 << 53) as f64 {
+        Ok(Value::from(number as i64))
+    } else {
+        Number::from_f64(number)
+            .map(Value::Number)
+            .ok_or_else(|| conversion_error(format!("Cannot convert {} to JSON", number)))
+    }
+}
+
+fn table_to_json(table: LuaTable, sort_keys: bool, tables: &mut TableTracker) -> LuaResult<Value> {
+    let mut entries = Vec::new();
+    for pair in table.pairs::<LuaValue, LuaValue>() {
+        entries.push(pair?);
+    }
+
+    let indices = entries.iter()
+        .map(|(key, _)| array_index(key))
+        .collect::<Option<Vec<_>>>();
+    if let Some(mut indices) = indices.filter(|indices| !indices.is_empty()) {
+        indices.sort_unstable();
+        if indices.iter().enumerate().all(|(position, index)| *index == position + 1) {
+            entries.sort_by_key(|(key, _)| array_index(key));
+            let values = entries.into_iter()
+                .map(|(_, value)| from_lua_value(value, sort_keys, tables))
+                .collect::<LuaResult<Vec<_>>>()?;
+            return Ok(Value::Array(values));
+        }
+    }
+
+    let mut keyed_entries = Vec::new();
+    for (key, value) in entries {
+        let key = match key {
+            LuaValue::String(string) => string.to_str()?.to_string(),
+            LuaValue::Integer(integer) => integer.to_string(),
+            LuaValue::Number(number) => number_to_json(number)?.to_string(),
+            other => return Err(conversion_error(format!("Cannot convert a table key of type '{}' to JSON", other.type_name())))
+        };
+        keyed_entries.push((key, value));
+    }
+    if sort_keys {
+        keyed_entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+
+    let mut result = Map::new();
+    for (key, value) in keyed_entries {
+        result.insert(key, from_lua_value(value, sort_keys, tables)?);
+    }
+
+    Ok(Value::Object(result))
+}
+
+/// Returns the key as an array index, if it is a positive whole number.
+fn array_index(key: &LuaValue) -> Option<usize> {
+    match key {
+        LuaValue::Integer(integer) if *integer >= 1 => Some(*integer as usize),
+        LuaValue::Number(number) if *number >= 1.0 && number.fract() == 0.0 => Some(*number as usize),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+    use mlua::prelude::LuaValue;
+
+    use crate::json::{from_lua, null, parse, stringify, to_lua};
+
+    fn lua_to_json(lua: &Lua, code: &str, sort_keys: bool) -> String {
+        let value = lua.load(code).eval::<LuaValue>().unwrap();
+        stringify(&from_lua(value, sort_keys).unwrap(), false).unwrap()
+    }
+
+    #[test]
+    fn sequences_should_become_arrays_and_other_tables_objects() {
+        let lua = Lua::new();
+        lua.globals().set("null", null()).unwrap();
+
+        assert_eq!("[1,2.5,\"three\",null]", lua_to_json(&lua, "{ 1, 2.5, 'three', null }", false));
+        assert_eq!("{\"1\":\"a\",\"3\":\"c\"}", lua_to_json(&lua, "{ [1] = 'a', [3] = 'c' }", true));
+        assert_eq!("{\"a\":[1],\"b\":{}}", lua_to_json(&lua, "{ b = {}, a = { 1 } }", true));
+        assert_eq!("{\"a\":true,\"b\":{\"c\":null}}", lua_to_json(&lua, "{ b = { c = null }, a = true }", true));
+    }
+
+    #[test]
+    fn cyclic_tables_should_not_be_converted() {
+        let lua = Lua::new();
+        let value = lua.load("local t = {} t.self = t return t").eval::<LuaValue>().unwrap();
+        let shared = lua.load("local t = {} return { t, t }").eval::<LuaValue>().unwrap();
+
+        assert!(from_lua(value, false).is_err());
+        assert!(from_lua(shared, false).is_ok());
+    }
+
+    #[test]
+    fn deeply_nested_tables_should_not_be_converted() {
+        let lua = Lua::new();
+        let value = lua.load("local t = {} for i = 1, 200000 do t = { t } end return t").eval::<LuaValue>().unwrap();
+
+        assert!(from_lua(value, false).is_err());
+    }
+
+    #[test]
+    fn json_should_round_trip_through_lua() {
+        let lua = Lua::new();
+        let text = r#"{"name":"mod","version":[1,2],"enabled":false,"parent":null,"scale":0.5}"#;
+
+        let value = to_lua(&lua, parse(text).unwrap()).unwrap();
+        let result = stringify(&from_lua(value, true).unwrap(), false).unwrap();
+
+        assert_eq!(r#"{"enabled":false,"name":"mod","parent":null,"scale":0.5,"version":[1,2]}"#, result);
+    }
+
+    #[test]
+    fn parse_errors_should_report_position() {
+        let error = parse("{\n  \"a\": 1,\n  \"b\": }").unwrap_err();
+
+        assert!(error.to_string().contains("line 3 column 8"), "{}", error);
+    }
+}
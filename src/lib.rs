@@ -1,16 +1,34 @@
 mod lua_exports;
+mod audit;
 mod file;
+mod file_handle;
+mod hash;
+mod conversion;
+mod json;
+mod lua_data;
+mod metadata;
+mod deny_list;
 mod directory;
+mod path_compare;
 mod path_filter;
+mod quota;
+mod scope;
+mod snapshot;
+mod sort;
+mod stats;
+mod transfer;
 mod util;
+mod walk;
+mod watcher;
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn luaopen_itb_io(lua_state: *mut mlua::lua_State) -> i32 {
     // Leak the Lua purposefully because it's supposed to live for the duration of the program.
     // It should be owned by the game, so as a client DLL, we can assume it's truly 'static.
     let lua = unsafe { mlua::Lua::init_from_ptr(lua_state) }.into_static();
 
-    let export = lua_exports::init(&lua).expect("Failed to initialize module export table");
+    let export = lua_exports::init(lua).expect("Failed to initialize module export table");
     lua.globals().set("itb_io", export).unwrap();
 
     0
@@ -0,0 +1,759 @@
+use std::cmp::Ordering;
+use std::io::{Error, ErrorKind};
+
+use mlua::{Integer, Lua, Value as LuaValue};
+use mlua::prelude::{LuaResult, LuaTable};
+
+use crate::conversion::{conversion_error, MAX_DEPTH, TableTracker};
+
+/// Names which can't be assigned to, because they're reserved by Lua.
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Value of a Lua data file, as far as constants are concerned.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LuaData {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    /// Lua strings are byte strings, which don't have to be valid UTF-8
+    String(Vec<u8>),
+    /// Entries in the order they appear in, with positional values given their implicit keys
+    Table(Vec<(LuaData, LuaData)>),
+}
+
+impl LuaData {
+    fn string<S: AsRef<[u8]>>(value: S) -> LuaData {
+        LuaData::String(value.as_ref().to_vec())
+    }
+}
+
+/// Parses the text of a Lua data file without executing anything. Supports the subset of Lua
+/// used by data files: constants, table constructors, and the `Point(x, y)` constructor used
+/// by the game, which produces a table with `x` and `y` keys.
+///
+/// The text is either a sequence of `Name = value` assignments, which are returned as a table
+/// keyed by the names, or a single `return value` statement, whose value is returned.
+pub fn parse<S: AsRef<[u8]>>(text: S) -> std::io::Result<LuaData> {
+    Parser { text: text.as_ref(), position: 0, depth: 0 }.parse_chunk()
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error<S: AsRef<str>>(&self, message: S) -> Error {
+        let consumed = &self.text[..self.position.min(self.text.len())];
+        let line = consumed.iter().filter(|byte| **byte == b'\n').count() + 1;
+        let column = consumed.iter().rev().take_while(|byte| **byte != b'\n').count() + 1;
+
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid Lua data at line {}, column {}: {}", line, column, message.as_ref())
+        )
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.text.get(self.position + offset).copied()
+    }
+
+    fn expect(&mut self, expected: u8) -> std::io::Result<()> {
+        self.skip_whitespace()?;
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("'{}' expected", expected as char)))
+        }
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_whitespace(&mut self) -> std::io::Result<()> {
+        loop {
+            match self.peek() {
+                Some(byte) if byte.is_ascii_whitespace() => self.position += 1,
+                Some(b'-') if self.peek_at(1) == Some(b'-') => {
+                    self.position += 2;
+                    if self.long_bracket_level().is_some() {
+                        self.parse_long_bracket()?;
+                    } else {
+                        while !matches!(self.peek(), None | Some(b'\n')) {
+                            self.position += 1;
+                        }
+                    }
+                }
+                _ => return Ok(())
+            }
+        }
+    }
+
+    fn parse_chunk(&mut self) -> std::io::Result<LuaData> {
+        let mut assignments = Vec::new();
+
+        loop {
+            self.skip_whitespace()?;
+            match self.peek() {
+                None => return Ok(LuaData::Table(assignments)),
+                Some(b';') => self.position += 1,
+                _ => {
+                    let name = self.parse_name()?;
+                    match name.as_str() {
+                        "return" => {
+                            let value = self.parse_value()?;
+                            self.skip_whitespace()?;
+                            if self.peek() == Some(b';') {
+                                self.position += 1;
+                                self.skip_whitespace()?;
+                            }
+                            if self.peek().is_some() {
+                                return Err(self.error("end of data expected after return statement"));
+                            }
+                            return Ok(value);
+                        }
+                        "local" => {
+                            let name = self.parse_name()?;
+                            self.parse_assignment(name, &mut assignments)?;
+                        }
+                        _ => self.parse_assignment(name, &mut assignments)?
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_assignment(&mut self, name: String, assignments: &mut Vec<(LuaData, LuaData)>) -> std::io::Result<()> {
+        self.expect(b'=')?;
+        let value = self.parse_value()?;
+        assignments.push((LuaData::string(name), value));
+        Ok(())
+    }
+
+    fn parse_name(&mut self) -> std::io::Result<String> {
+        self.skip_whitespace()?;
+        let start = self.position;
+        while let Some(byte) = self.peek() {
+            let is_name_byte = byte == b'_' || byte.is_ascii_alphabetic()
+                || (self.position > start && byte.is_ascii_digit());
+            if !is_name_byte {
+                break;
+            }
+            self.position += 1;
+        }
+
+        if self.position == start {
+            Err(self.error("name expected"))
+        } else {
+            Ok(String::from_utf8_lossy(&self.text[start..self.position]).into_owned())
+        }
+    }
+
+    fn parse_value(&mut self) -> std::io::Result<LuaData> {
+        // Every kind of value which contains others is parsed recursively through here
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("values are nested too deeply"));
+        }
+        let result = self.parse_single_value();
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_single_value(&mut self) -> std::io::Result<LuaData> {
+        self.skip_whitespace()?;
+        match self.peek() {
+            None => Err(self.error("value expected")),
+            Some(b'{') => self.parse_table(),
+            Some(b'"') | Some(b'\'') => self.parse_string(),
+            Some(b'[') if self.long_bracket_level().is_some() => Ok(LuaData::String(self.parse_long_bracket()?)),
+            Some(b'-') => {
+                self.position += 1;
+                match self.parse_value()? {
+                    LuaData::Integer(integer) => Ok(LuaData::Integer(integer.wrapping_neg())),
+                    LuaData::Number(number) => Ok(LuaData::Number(-number)),
+                    _ => Err(self.error("only numbers can be negated"))
+                }
+            }
+            Some(byte) if byte.is_ascii_digit() => self.parse_number(),
+            Some(b'.') if self.peek_at(1).is_some_and(|byte| byte.is_ascii_digit()) => self.parse_number(),
+            Some(byte) if byte == b'_' || byte.is_ascii_alphabetic() => {
+                let start = self.position;
+                match self.parse_name()?.as_str() {
+                    "nil" => Ok(LuaData::Nil),
+                    "true" => Ok(LuaData::Boolean(true)),
+                    "false" => Ok(LuaData::Boolean(false)),
+                    "Point" => self.parse_point(),
+                    name => {
+                        self.position = start;
+                        Err(self.error(format!("unexpected name '{}', only constants are allowed", name)))
+                    }
+                }
+            }
+            Some(byte) => Err(self.error(format!("unexpected character '{}'", byte as char)))
+        }
+    }
+
+    fn parse_point(&mut self) -> std::io::Result<LuaData> {
+        self.expect(b'(')?;
+        let x = self.parse_value()?;
+        self.expect(b',')?;
+        let y = self.parse_value()?;
+        self.expect(b')')?;
+
+        Ok(LuaData::Table(vec![
+            (LuaData::string("x"), x),
+            (LuaData::string("y"), y),
+        ]))
+    }
+
+    fn parse_table(&mut self) -> std::io::Result<LuaData> {
+        self.expect(b'{')?;
+
+        let mut entries = Vec::new();
+        let mut next_index = 1;
+        loop {
+            self.skip_whitespace()?;
+            if self.peek() == Some(b'}') {
+                self.position += 1;
+                break;
+            }
+
+            if self.peek() == Some(b'[') && self.long_bracket_level().is_none() {
+                self.position += 1;
+                let key = self.parse_value()?;
+                self.expect(b']')?;
+                self.expect(b'=')?;
+                entries.push((key, self.parse_value()?));
+            } else if self.is_name_followed_by_assignment() {
+                let name = self.parse_name()?;
+                self.expect(b'=')?;
+                entries.push((LuaData::string(name), self.parse_value()?));
+            } else {
+                entries.push((LuaData::Integer(next_index), self.parse_value()?));
+                next_index += 1;
+            }
+
+            self.skip_whitespace()?;
+            match self.peek() {
+                Some(b',') | Some(b';') => self.position += 1,
+                Some(b'}') => {}
+                _ => return Err(self.error("'}' expected"))
+            }
+        }
+
+        Ok(LuaData::Table(entries))
+    }
+
+    /// Returns true if the upcoming tokens are `name =`, as opposed to a positional value
+    /// like `true` or `Point(1, 2)`.
+    fn is_name_followed_by_assignment(&mut self) -> bool {
+        let start = self.position;
+        let result = self.parse_name().is_ok()
+            && self.skip_whitespace().is_ok()
+            && self.peek() == Some(b'=')
+            && self.peek_at(1) != Some(b'=');
+        self.position = start;
+        result
+    }
+
+    fn parse_number(&mut self) -> std::io::Result<LuaData> {
+        let start = self.position;
+        let is_hex = self.peek() == Some(b'0') && matches!(self.peek_at(1), Some(b'x') | Some(b'X'));
+        if is_hex {
+            self.position += 2;
+        }
+
+        while let Some(byte) = self.peek() {
+            let is_exponent_sign = matches!(byte, b'+' | b'-')
+                && !is_hex
+                && matches!(self.text[self.position - 1], b'e' | b'E');
+            if byte.is_ascii_alphanumeric() || byte == b'.' || is_exponent_sign {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        let literal = String::from_utf8_lossy(&self.text[start..self.position]).into_owned();
+        let result = if is_hex {
+            u64::from_str_radix(&literal[2..], 16)
+                .map(|value| LuaData::Integer(value as i64))
+                .ok()
+        } else if literal.contains(['.', 'e', 'E']) {
+            literal.parse().map(LuaData::Number).ok()
+        } else {
+            literal.parse().map(LuaData::Integer)
+                .or_else(|_| literal.parse().map(LuaData::Number))
+                .ok()
+        };
+
+        result.ok_or_else(|| {
+            self.position = start;
+            self.error(format!("malformed number '{}'", literal))
+        })
+    }
+
+    fn parse_string(&mut self) -> std::io::Result<LuaData> {
+        let quote = self.text[self.position];
+        self.position += 1;
+
+        let mut result = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => return Err(self.error("unfinished string")),
+                Some(byte) if byte == quote => {
+                    self.position += 1;
+                    return Ok(LuaData::String(result));
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escaped = self.peek().ok_or_else(|| self.error("unfinished string"))?;
+                    self.position += 1;
+                    match escaped {
+                        b'n' => result.push(b'\n'),
+                        b't' => result.push(b'\t'),
+                        b'r' => result.push(b'\r'),
+                        b'a' => result.push(0x07),
+                        b'b' => result.push(0x08),
+                        b'f' => result.push(0x0c),
+                        b'v' => result.push(0x0b),
+                        b'\\' | b'"' | b'\'' | b'\n' => result.push(escaped),
+                        b'0'..=b'9' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(digit) if digit.is_ascii_digit() => {
+                                        value = value * 10 + (digit - b'0') as u32;
+                                        self.position += 1;
+                                    }
+                                    _ => break
+                                }
+                            }
+                            let byte = u8::try_from(value)
+                                .map_err(|_| self.error("escape sequence too large"))?;
+                            result.push(byte);
+                        }
+                        _ => return Err(self.error(format!("invalid escape sequence '\\{}'", escaped as char)))
+                    }
+                }
+                Some(byte) => {
+                    result.push(byte);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    /// Returns the level of the long bracket starting at the current position, like 2 for `[==[`.
+    fn long_bracket_level(&self) -> Option<usize> {
+        if self.peek() != Some(b'[') {
+            return None;
+        }
+
+        let level = self.text[self.position + 1..].iter()
+            .take_while(|byte| **byte == b'=')
+            .count();
+        if self.peek_at(level + 1) == Some(b'[') {
+            Some(level)
+        } else {
+            None
+        }
+    }
+
+    /// Parses the content of a long string or comment, like `[[text]]` or `[==[text]==]`.
+    fn parse_long_bracket(&mut self) -> std::io::Result<Vec<u8>> {
+        let level = self.long_bracket_level().ok_or_else(|| self.error("long bracket expected"))?;
+        self.position += level + 2;
+        // A newline immediately following the opening bracket is not part of the content
+        if self.peek() == Some(b'\r') {
+            self.position += 1;
+        }
+        if self.peek() == Some(b'\n') {
+            self.position += 1;
+        }
+
+        let mut closing = vec![b']'];
+        closing.extend(std::iter::repeat_n(b'=', level));
+        closing.push(b']');
+
+        let start = self.position;
+        match self.text[start..].windows(closing.len()).position(|window| window == closing.as_slice()) {
+            Some(length) => {
+                self.position = start + length + closing.len();
+                Ok(self.text[start..start + length].to_vec())
+            }
+            None => Err(self.error("unfinished long string or comment"))
+        }
+    }
+}
+
+/// Options for serializing Lua data.
+#[derive(Clone, Debug)]
+pub struct SerializeOptions {
+    /// Text each level of nesting is indented with
+    pub indent: String,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        SerializeOptions {
+            indent: "\t".to_string(),
+        }
+    }
+}
+
+/// Serializes data into the text of a Lua data file, which can be read back with `parse`.
+/// The data is assigned to the given name, or returned if no name is given.
+///
+/// The output is deterministic: table entries are written with explicit keys, numeric keys
+/// first in ascending order, then string keys in byte order, then boolean keys.
+pub fn serialize(name: Option<&str>, data: &LuaData, options: &SerializeOptions) -> std::io::Result<String> {
+    let mut result = match name {
+        Some(name) => {
+            let is_valid_name = name.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic())
+                && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric())
+                && !KEYWORDS.contains(&name);
+            if !is_valid_name {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("'{}' is not a valid Lua name", name)));
+            }
+            format!("{} = ", name)
+        }
+        None => "return ".to_string()
+    };
+
+    write_data(&mut result, data, options, 0)?;
+    result.push('\n');
+    Ok(result)
+}
+
+fn write_data(out: &mut String, data: &LuaData, options: &SerializeOptions, depth: usize) -> std::io::Result<()> {
+    match data {
+        LuaData::Nil => out.push_str("nil"),
+        LuaData::Boolean(value) => out.push_str(if *value { "true" } else { "false" }),
+        LuaData::Integer(integer) => out.push_str(&integer.to_string()),
+        LuaData::Number(number) => {
+            if number.is_nan() {
+                return Err(Error::new(ErrorKind::InvalidInput, "NaN cannot be written as Lua data"));
+            } else if number.is_infinite() {
+                // Overflows to infinity when read back
+                out.push_str(if *number > 0.0 { "1e999" } else { "-1e999" });
+            } else {
+                // Shortest representation which reads back as exactly the same number
+                out.push_str(&number.to_string());
+            }
+        }
+        LuaData::String(string) => write_string(out, string),
+        LuaData::Table(_) if depth >= MAX_DEPTH => {
+            return Err(Error::new(ErrorKind::InvalidInput, "Cannot write tables nested this deeply as Lua data"));
+        }
+        LuaData::Table(entries) if entries.is_empty() => out.push_str("{}"),
+        LuaData::Table(entries) => {
+            let mut entries = entries.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| compare_keys(a, b));
+
+            out.push_str("{\n");
+            for (key, value) in entries {
+                out.push_str(&options.indent.repeat(depth + 1));
+                out.push('[');
+                write_data(out, key, options, depth + 1)?;
+                out.push_str("] = ");
+                write_data(out, value, options, depth + 1)?;
+                out.push_str(",\n");
+            }
+            out.push_str(&options.indent.repeat(depth));
+            out.push('}');
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a quoted string, escaping anything which isn't printable with decimal escapes,
+/// so that the output stays on one line and reads back byte for byte.
+fn write_string(out: &mut String, string: &[u8]) {
+    out.push('"');
+    for chunk in string.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                // Three digits, so that a following digit isn't taken as part of the escape
+                c if c.is_ascii_control() => out.push_str(&format!("\\{:03}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\{:03}", byte));
+        }
+    }
+    out.push('"');
+}
+
+fn compare_keys(a: &LuaData, b: &LuaData) -> Ordering {
+    fn rank(key: &LuaData) -> u8 {
+        match key {
+            LuaData::Integer(_) | LuaData::Number(_) => 0,
+            LuaData::String(_) => 1,
+            LuaData::Boolean(_) => 2,
+            _ => 3,
+        }
+    }
+
+    match (a, b) {
+        (LuaData::Integer(a), LuaData::Integer(b)) => a.cmp(b),
+        (LuaData::Integer(_) | LuaData::Number(_), LuaData::Integer(_) | LuaData::Number(_)) => {
+            as_number(a).total_cmp(&as_number(b))
+        }
+        (LuaData::String(a), LuaData::String(b)) => a.cmp(b),
+        (LuaData::Boolean(a), LuaData::Boolean(b)) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn as_number(data: &LuaData) -> f64 {
+    match data {
+        LuaData::Integer(integer) => *integer as f64,
+        LuaData::Number(number) => *number,
+        _ => f64::NAN,
+    }
+}
+
+/// Converts Lua values into data which can be serialized. Only constants and tables of them
+/// are supported, and tables can't contain themselves.
+pub(crate) fn from_lua(value: LuaValue) -> LuaResult<LuaData> {
+    from_lua_value(value, &mut TableTracker::new("Lua data"))
+}
+
+fn from_lua_value(value: LuaValue, tables: &mut TableTracker) -> LuaResult<LuaData> {
+    let result = match value {
+        LuaValue::Nil => LuaData::Nil,
+        LuaValue::Boolean(value) => LuaData::Boolean(value),
+        // Lua's integers are only 32 bits wide on 32-bit targets
+        #[allow(clippy::useless_conversion)]
+        LuaValue::Integer(integer) => LuaData::Integer(i64::from(integer)),
+        LuaValue::Number(number) => LuaData::Number(number),
+        LuaValue::String(string) => LuaData::String(string.as_bytes().to_vec()),
+        LuaValue::Table(table) => tables.convert(table, table_from_lua)?,
+        other => return Err(conversion_error(format!("Cannot write a value of type '{}' as Lua data", other.type_name())))
+    };
+
+    Ok(result)
+}
+
+fn table_from_lua(table: LuaTable, tables: &mut TableTracker) -> LuaResult<LuaData> {
+    let mut entries = Vec::new();
+    for pair in table.pairs::<LuaValue, LuaValue>() {
+        let (key, value) = pair?;
+        if matches!(key, LuaValue::Table(_)) {
+            return Err(conversion_error("Cannot write a table with table keys as Lua data"));
+        }
+        entries.push((from_lua_value(key, tables)?, from_lua_value(value, tables)?));
+    }
+
+    Ok(LuaData::Table(entries))
+}
+
+/// Converts parsed data into Lua values.
+pub(crate) fn to_lua(lua: &Lua, data: LuaData) -> LuaResult<LuaValue<'_>> {
+    let result = match data {
+        LuaData::Nil => LuaValue::Nil,
+        LuaData::Boolean(value) => LuaValue::Boolean(value),
+        // Lua's integers are only 32 bits wide on 32-bit targets, so larger ones become numbers
+        LuaData::Integer(integer) => Integer::try_from(integer)
+            .map(LuaValue::Integer)
+            .unwrap_or(LuaValue::Number(integer as f64)),
+        LuaData::Number(number) => LuaValue::Number(number),
+        LuaData::String(string) => LuaValue::String(lua.create_string(&string)?),
+        LuaData::Table(entries) => {
+            let table = lua.create_table()?;
+            for (key, value) in entries {
+                // Like in Lua, a later entry with the same key overrides an earlier one
+                if key != LuaData::Nil {
+                    table.raw_set(to_lua(lua, key)?, to_lua(lua, value)?)?;
+                }
+            }
+            LuaValue::Table(table)
+        }
+    };
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+    use mlua::prelude::LuaValue;
+
+    use crate::lua_data::{from_lua, LuaData, parse, serialize, SerializeOptions};
+
+    fn string(value: &str) -> LuaData {
+        LuaData::string(value)
+    }
+
+    fn field(name: &str, value: LuaData) -> (LuaData, LuaData) {
+        (string(name), value)
+    }
+
+    #[test]
+    fn assignments_should_be_parsed_into_table() {
+        let text = r#"
+            -- Saved by the game
+            GameData = {["network"] = 7, ["current"] = {["pos"] = Point(3, -4), }, }
+            local Options = { music = false; 1, 2.5, "three", }
+        "#;
+
+        let result = parse(text).unwrap();
+
+        assert_eq!(LuaData::Table(vec![
+            field("GameData", LuaData::Table(vec![
+                field("network", LuaData::Integer(7)),
+                field("current", LuaData::Table(vec![
+                    field("pos", LuaData::Table(vec![
+                        field("x", LuaData::Integer(3)),
+                        field("y", LuaData::Integer(-4)),
+                    ])),
+                ])),
+            ])),
+            field("Options", LuaData::Table(vec![
+                field("music", LuaData::Boolean(false)),
+                (LuaData::Integer(1), LuaData::Integer(1)),
+                (LuaData::Integer(2), LuaData::Number(2.5)),
+                (LuaData::Integer(3), string("three")),
+            ])),
+        ]), result);
+    }
+
+    #[test]
+    fn return_statement_should_produce_its_value() {
+        let result = parse("--[==[ header\n]] still a comment ]==]\nreturn { [[\nlong]], [=[a]]b]=] };").unwrap();
+
+        assert_eq!(LuaData::Table(vec![
+            (LuaData::Integer(1), string("long")),
+            (LuaData::Integer(2), string("a]]b")),
+        ]), result);
+    }
+
+    #[test]
+    fn literals_should_follow_lua_syntax() {
+        let result = parse(r#"return { 0x1F, 1e3, .5, -2.5E-1, - -3, "a\tb\\\"c\65\0", 'it\'s', nil, true }"#).unwrap();
+
+        assert_eq!(LuaData::Table(vec![
+            (LuaData::Integer(1), LuaData::Integer(31)),
+            (LuaData::Integer(2), LuaData::Number(1000.0)),
+            (LuaData::Integer(3), LuaData::Number(0.5)),
+            (LuaData::Integer(4), LuaData::Number(-0.25)),
+            (LuaData::Integer(5), LuaData::Integer(3)),
+            (LuaData::Integer(6), LuaData::String(b"a\tb\\\"cA\0".to_vec())),
+            (LuaData::Integer(7), string("it's")),
+            (LuaData::Integer(8), LuaData::Nil),
+            (LuaData::Integer(9), LuaData::Boolean(true)),
+        ]), result);
+    }
+
+    #[test]
+    fn code_should_be_rejected_without_being_executed() {
+        let error = parse("Data = {\n  value = os.execute('rm -rf /'),\n}").unwrap_err();
+
+        assert!(error.to_string().contains("line 2, column 11"), "{}", error);
+        assert!(parse("Data = dofile('x.lua')").is_err());
+        assert!(parse("Data = { 1 + 2 }").is_err());
+        assert!(parse("return {} Data = {}").is_err());
+        assert!(parse("Data = \"unfinished").is_err());
+    }
+
+    #[test]
+    fn deeply_nested_tables_should_be_rejected() {
+        let text = format!("return {}{}", "{".repeat(1000), "}".repeat(1000));
+
+        assert!(parse(text).is_err());
+    }
+
+    #[test]
+    fn deeply_nested_negations_and_points_should_be_rejected() {
+        let negations = format!("return {}1", "- ".repeat(100_000));
+        let points = format!("return {}1, 2{}", "Point(".repeat(100_000), ")".repeat(100_000));
+
+        assert!(parse(negations).is_err());
+        assert!(parse(points).is_err());
+        assert_eq!(LuaData::Integer(1), parse("return - - 1").unwrap());
+    }
+
+    #[test]
+    fn serialized_data_should_be_sorted_and_indented() {
+        let data = LuaData::Table(vec![
+            field("name", string("Rift \"Walkers\"\n")),
+            (LuaData::Integer(2), LuaData::Number(0.5)),
+            (LuaData::Boolean(true), LuaData::Table(Vec::new())),
+            (LuaData::Integer(1), LuaData::Table(vec![field("x", LuaData::Integer(-1))])),
+            field("enabled", LuaData::Boolean(false)),
+        ]);
+        let options = SerializeOptions { indent: "  ".to_string() };
+
+        let result = serialize(Some("Config"), &data, &options).unwrap();
+
+        assert_eq!(concat!(
+            "Config = {\n",
+            "  [1] = {\n",
+            "    [\"x\"] = -1,\n",
+            "  },\n",
+            "  [2] = 0.5,\n",
+            "  [\"enabled\"] = false,\n",
+            "  [\"name\"] = \"Rift \\\"Walkers\\\"\\n\",\n",
+            "  [true] = {},\n",
+            "}\n",
+        ), result);
+    }
+
+    #[test]
+    fn serialized_data_should_parse_back_identically() {
+        let data = LuaData::Table(vec![
+            (LuaData::Integer(1), LuaData::String(b"\x00\x01\x7f1\xff\\ \xc3\xa9".to_vec())),
+            (LuaData::Integer(2), LuaData::Number(0.1)),
+            (LuaData::Integer(3), LuaData::Number(f64::INFINITY)),
+            (LuaData::Integer(4), LuaData::Integer(i64::MIN + 1)),
+            field("nested", LuaData::Table(vec![field("deeper", LuaData::Table(vec![field("x", LuaData::Boolean(true))]))])),
+        ]);
+
+        let text = serialize(None, &data, &SerializeOptions::default()).unwrap();
+
+        assert_eq!(data, parse(text).unwrap());
+    }
+
+    #[test]
+    fn invalid_names_and_cyclic_tables_should_not_be_serialized() {
+        let lua = Lua::new();
+        let cyclic = lua.load("local t = {} t[1] = { t } return t").eval::<LuaValue>().unwrap();
+        let shared = lua.load("local t = { 1 } return { a = t, b = t }").eval::<LuaValue>().unwrap();
+
+        assert!(from_lua(cyclic).is_err());
+        assert!(from_lua(shared).is_ok());
+        assert!(serialize(Some("end"), &LuaData::Nil, &SerializeOptions::default()).is_err());
+        assert!(serialize(Some("1st"), &LuaData::Nil, &SerializeOptions::default()).is_err());
+        assert!(serialize(Some("my config"), &LuaData::Nil, &SerializeOptions::default()).is_err());
+    }
+
+    #[test]
+    fn deeply_nested_values_should_not_be_converted_or_serialized() {
+        let lua = Lua::new();
+        let nested = lua.load("local t = {} for i = 1, 200000 do t = { t } end return t").eval::<LuaValue>().unwrap();
+        let mut data = LuaData::Nil;
+        for _ in 0..1000 {
+            data = LuaData::Table(vec![(LuaData::Integer(1), data)]);
+        }
+
+        assert!(from_lua(nested).is_err());
+        assert!(serialize(None, &data, &SerializeOptions::default()).is_err());
+    }
+}
@@ -2,48 +2,76 @@ use std::error::Error;
 use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 
+use globset::GlobMatcher;
 use mlua::{Lua, UserDataMethods, Variadic};
-use mlua::prelude::{LuaError, LuaResult, LuaTable, LuaUserData};
+use mlua::prelude::{LuaError, LuaFunction, LuaResult, LuaString, LuaTable, LuaUserData, LuaValue};
 use path_absolutize::Absolutize;
 
+use crate::audit;
+use crate::deny_list;
 use crate::directory::Directory;
 use crate::file::File;
-use crate::path_filter::PathFilter;
+use crate::file_handle::{FileHandle, OpenMode, Whence};
+use crate::hash::HashAlgorithm;
+use crate::json;
+use crate::lua_data;
+use crate::lua_data::SerializeOptions;
+use crate::metadata::Metadata;
+use crate::path_filter::{PathFilter, Permission};
+use crate::quota;
+use crate::scope::Scope;
+use crate::snapshot::Snapshot;
+use crate::sort::{SortDirection, SortKey, SortOrder};
+use crate::stats::DirectoryStats;
+use crate::transfer::{OverwritePolicy, ProgressCallback, TransferOptions};
+use crate::walk::{compile_glob, DirectoryEntry, WalkOptions};
+use crate::watcher::DirectoryWatcher;
+
+/// Number of entries returned by `audit_entries` when no count is specified.
+const DEFAULT_AUDIT_ENTRY_COUNT: usize = 50;
+/// Algorithm used by `hash_tree` when none is specified.
+const DEFAULT_HASH_ALGORITHM: &str = "sha256";
 
 /// Build the module's exports table, governing what is exposed to Lua.
-pub fn init(lua: &Lua) -> LuaResult<LuaTable> {
+pub fn init(lua: &Lua) -> LuaResult<LuaTable<'_>> {
     let exports = lua.create_table()?;
 
     exports.set("file", lua.create_function(lua_file)?)?;
     exports.set("directory", lua.create_function(lua_directory)?)?;
     exports.set("save_data_directory", lua.create_function(save_data_directory)?)?;
+    exports.set("set_save_data_directory", lua.create_function(set_save_data_directory)?)?;
+    exports.set("lock", lua.create_function(lock)?)?;
+    exports.set("is_locked", lua.create_function(is_locked)?)?;
+    exports.set("register_root", lua.create_function(register_root)?)?;
+    exports.set("root", lua.create_function(root)?)?;
+    exports.set("roots", lua.create_function(roots)?)?;
+    exports.set("scope", lua.create_function(scope)?)?;
+    exports.set("deny", lua.create_function(deny)?)?;
+    exports.set("freeze_deny_rules", lua.create_function(freeze_deny_rules)?)?;
+    exports.set("set_quota", lua.create_function(set_quota)?)?;
+    exports.set("usage", lua.create_function(usage)?)?;
+    exports.set("remaining", lua.create_function(remaining)?)?;
+    exports.set("enable_audit", lua.create_function(enable_audit)?)?;
+    exports.set("disable_audit", lua.create_function(disable_audit)?)?;
+    exports.set("is_audit_enabled", lua.create_function(is_audit_enabled)?)?;
+    exports.set("audit_entries", lua.create_function(audit_entries)?)?;
+    exports.set("parse_snapshot", lua.create_function(parse_snapshot)?)?;
+    exports.set("null", json::null())?;
 
     Ok(exports)
 }
 
 //region <Exported adapter functions>
-fn lua_file(_: &Lua, (maybe_path, ): (Option<String>, )) -> LuaResult<File> {
-    let path = match maybe_path {
-        None => ".".to_string(),
-        Some(path) => path
-    };
-
-    let path = normalize(PathBuf::from(path));
-    let normalized_path = path.absolutize()
+fn lua_file(_: &Lua, (maybe_path, maybe_root): (Option<String>, Option<String>)) -> LuaResult<File> {
+    let normalized_path = resolve_lua_path(maybe_path, maybe_root)
         .map_err(external_lua_error)?;
 
     file(normalized_path)
         .map_err(external_lua_error)
 }
 
-fn lua_directory(_: &Lua, (maybe_path, ): (Option<String>, )) -> LuaResult<Directory> {
-    let path = match maybe_path {
-        None => ".".to_string(),
-        Some(path) => path
-    };
-
-    let path = normalize(PathBuf::from(path));
-    let normalized_path = path.absolutize()
+fn lua_directory(_: &Lua, (maybe_path, maybe_root): (Option<String>, Option<String>)) -> LuaResult<Directory> {
+    let normalized_path = resolve_lua_path(maybe_path, maybe_root)
         .map_err(external_lua_error)?;
 
     directory(normalized_path)
@@ -55,6 +83,128 @@ fn save_data_directory(_: &Lua, (): ()) -> LuaResult<Directory> {
         .map(Directory::from)
         .map_err(external_lua_error)
 }
+
+fn set_save_data_directory(_: &Lua, (path, ): (String, )) -> LuaResult<()> {
+    let path = normalize(PathBuf::from(path));
+
+    PathFilter::set_save_data_directory(path)
+        .map_err(external_lua_error)
+}
+
+fn lock(_: &Lua, (): ()) -> LuaResult<()> {
+    PathFilter::lock();
+    Ok(())
+}
+
+fn is_locked(_: &Lua, (): ()) -> LuaResult<bool> {
+    Ok(PathFilter::is_locked())
+}
+
+fn register_root(_: &Lua, (name, path, writable): (String, String, Option<bool>)) -> LuaResult<Directory> {
+    let path = normalize(PathBuf::from(path));
+    let permission = if writable.unwrap_or(false) {
+        Permission::ReadWrite
+    } else {
+        Permission::ReadOnly
+    };
+
+    PathFilter::register_root(name, path, permission)
+        .map(|root| Directory::from(root.path))
+        .map_err(external_lua_error)
+}
+
+fn root(_: &Lua, (name, ): (String, )) -> LuaResult<Option<Directory>> {
+    PathFilter::root(name)
+        .map(|maybe_root| maybe_root.map(|root| Directory::from(root.path)))
+        .map_err(external_lua_error)
+}
+
+fn scope(_: &Lua, (mod_id, options): (String, Option<LuaTable>)) -> LuaResult<Scope> {
+    let (mod_directory, quota) = match options {
+        Some(options) => {
+            let mod_directory = options.get::<_, Option<String>>("directory")?
+                .map(|path| resolve_lua_path(Some(path), None))
+                .transpose()
+                .map_err(external_lua_error)?;
+
+            (mod_directory, options.get::<_, Option<u64>>("quota")?)
+        }
+        None => (None, None)
+    };
+
+    Scope::new(mod_id, mod_directory, quota)
+        .map_err(external_lua_error)
+}
+
+fn deny(_: &Lua, (pattern, root_name): (String, Option<String>)) -> LuaResult<()> {
+    deny_list::deny(pattern, root_name)
+        .map_err(external_lua_error)
+}
+
+fn freeze_deny_rules(_: &Lua, (): ()) -> LuaResult<()> {
+    deny_list::freeze();
+    Ok(())
+}
+
+fn set_quota(_: &Lua, (root_name, limit): (String, u64)) -> LuaResult<()> {
+    quota::set_root_quota(root_name, limit)
+        .map_err(external_lua_error)
+}
+
+fn usage(_: &Lua, (root_name, ): (String, )) -> LuaResult<Option<u64>> {
+    Ok(quota::root_quota(root_name).map(|quota| quota.usage()))
+}
+
+fn remaining(_: &Lua, (root_name, ): (String, )) -> LuaResult<Option<u64>> {
+    Ok(quota::root_quota(root_name).map(|quota| quota.remaining()))
+}
+
+fn enable_audit(_: &Lua, (max_size, ): (Option<u64>, )) -> LuaResult<()> {
+    audit::enable(max_size)
+        .map_err(external_lua_error)
+}
+
+fn disable_audit(_: &Lua, (): ()) -> LuaResult<()> {
+    audit::disable()
+        .map_err(external_lua_error)
+}
+
+fn is_audit_enabled(_: &Lua, (): ()) -> LuaResult<bool> {
+    Ok(audit::is_enabled())
+}
+
+fn audit_entries(lua: &Lua, (count, ): (Option<usize>, )) -> LuaResult<LuaTable<'_>> {
+    let entries = audit::recent_entries(count.unwrap_or(DEFAULT_AUDIT_ENTRY_COUNT))
+        .map_err(external_lua_error)?;
+
+    let result = lua.create_table()?;
+    for entry in entries {
+        let entry_table = lua.create_table()?;
+        entry_table.set("timestamp", entry.timestamp)?;
+        entry_table.set("operation", entry.operation)?;
+        entry_table.set("path", entry.path)?;
+        entry_table.set("destination", entry.destination)?;
+        entry_table.set("bytes", entry.bytes)?;
+        entry_table.set("error", entry.error)?;
+        result.push(entry_table)?;
+    }
+
+    Ok(result)
+}
+
+fn parse_snapshot(_: &Lua, (text, ): (String, )) -> LuaResult<Snapshot> {
+    Snapshot::parse(text)
+        .map_err(external_lua_error)
+}
+
+fn roots(lua: &Lua, (): ()) -> LuaResult<LuaTable<'_>> {
+    let result = lua.create_table()?;
+    for root in PathFilter::roots().map_err(external_lua_error)? {
+        result.set(root.name, Directory::from(root.path))?;
+    }
+
+    Ok(result)
+}
 //endregion
 
 fn external_lua_error<T: Error + Send + Sync + 'static>(error: T) -> LuaError {
@@ -65,7 +215,7 @@ fn file<P: AsRef<Path>>(path: P) -> std::io::Result<File> where PathBuf: From<P>
     if PathFilter::is_whitelisted(&path)? {
         Ok(File::from(path))
     } else {
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "Path points to a file not within an allowed directory"))
+        Err(std::io::Error::other("Path points to a file not within an allowed directory"))
     }
 }
 
@@ -73,12 +223,151 @@ fn directory<P: AsRef<Path>>(path: P) -> std::io::Result<Directory> where PathBu
     if PathFilter::is_whitelisted(&path)? {
         Ok(Directory::from(path))
     } else {
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "Path does not point to an allowed directory"))
+        Err(std::io::Error::other("Path does not point to an allowed directory"))
+    }
+}
+
+/// Turns a path received from Lua into an absolute path. Relative paths are resolved against
+/// the root with the given name if one was specified, or against the game directory otherwise.
+fn resolve_lua_path(maybe_path: Option<String>, maybe_root: Option<String>) -> std::io::Result<PathBuf> {
+    let path = match maybe_path {
+        None => ".".to_string(),
+        Some(path) => path
+    };
+
+    match maybe_root {
+        None => {
+            let path = normalize(PathBuf::from(path));
+            Ok(path.absolutize()?.to_path_buf())
+        }
+        Some(root_name) => {
+            let root = PathFilter::root(&root_name)?
+                .ok_or_else(|| std::io::Error::other(format!("No root named '{}' is registered", root_name)))?;
+            let path = root.path.join(path);
+            let normalized_path = path.absolutize()?.to_path_buf();
+
+            if root.contains(&normalized_path)? {
+                Ok(normalized_path)
+            } else {
+                Err(std::io::Error::other(format!("Path is not within the '{}' root", root_name)))
+            }
+        }
+    }
+}
+
+/// Parses a sort key and direction received from Lua, each defaulting to that of `SortOrder`.
+fn sort_order(key: Option<String>, direction: Option<String>) -> LuaResult<SortOrder> {
+    let default = SortOrder::default();
+    let key = key.map(|key| key.parse::<SortKey>())
+        .transpose()
+        .map_err(external_lua_error)?
+        .unwrap_or(default.key);
+    let direction = direction.map(|direction| direction.parse::<SortDirection>())
+        .transpose()
+        .map_err(external_lua_error)?
+        .unwrap_or(default.direction);
+
+    Ok(SortOrder::new(key, direction))
+}
+
+fn walk_options(options: LuaTable) -> LuaResult<WalkOptions> {
+    let glob = |key: &str| -> LuaResult<Option<GlobMatcher>> {
+        options.get::<_, Option<String>>(key)?
+            .map(compile_glob)
+            .transpose()
+            .map_err(external_lua_error)
+    };
+    let order = sort_order(options.get("sort")?, options.get("direction")?)?;
+
+    Ok(WalkOptions {
+        max_depth: options.get("max_depth")?,
+        include: glob("include")?,
+        exclude: glob("exclude")?,
+        files: options.get::<_, Option<bool>>("files")?.unwrap_or(true),
+        directories: options.get::<_, Option<bool>>("dirs")?.unwrap_or(true),
+        order,
+    })
+}
+
+fn entries_table(lua: &Lua, entries: Vec<DirectoryEntry>) -> LuaResult<LuaTable<'_>> {
+    let result = lua.create_table()?;
+    for entry in entries {
+        match entry {
+            DirectoryEntry::File(file) => result.push(file)?,
+            DirectoryEntry::Directory(directory) => result.push(directory)?,
+        }
+    }
+
+    Ok(result)
+}
+
+fn transfer_options(options: Option<LuaTable>) -> LuaResult<TransferOptions> {
+    let options = match options {
+        Some(options) => options,
+        None => return Ok(TransferOptions::default())
+    };
+
+    let overwrite = options.get::<_, Option<String>>("overwrite")?
+        .map(|overwrite| overwrite.parse::<OverwritePolicy>())
+        .transpose()
+        .map_err(external_lua_error)?
+        .unwrap_or(TransferOptions::default().overwrite);
+    let skip = options.get::<_, Option<String>>("skip")?
+        .map(compile_glob)
+        .transpose()
+        .map_err(external_lua_error)?;
+    let progress = options.get::<_, Option<LuaFunction>>("progress")?
+        .map(|callback| -> ProgressCallback {
+            Box::new(move |path, done, total| {
+                callback.call::<_, ()>((path, done, total))
+                    .map_err(|error| std::io::Error::other(error.to_string()))
+            })
+        });
+
+    Ok(TransferOptions { overwrite, skip, progress })
+}
+
+fn stats_table(lua: &Lua, stats: DirectoryStats) -> LuaResult<LuaTable<'_>> {
+    let largest_files = lua.create_table()?;
+    for (path, size) in stats.largest_files {
+        let file_table = lua.create_table()?;
+        file_table.set("path", path)?;
+        file_table.set("size", size)?;
+        largest_files.push(file_table)?;
+    }
+
+    let extensions = lua.create_table()?;
+    for (extension, extension_stats) in stats.extensions {
+        let extension_table = lua.create_table()?;
+        extension_table.set("bytes", extension_stats.bytes)?;
+        extension_table.set("file_count", extension_stats.file_count)?;
+        extensions.set(extension, extension_table)?;
     }
+
+    let result = lua.create_table()?;
+    result.set("total_bytes", stats.total_bytes)?;
+    result.set("file_count", stats.file_count)?;
+    result.set("directory_count", stats.directory_count)?;
+    result.set("largest_files", largest_files)?;
+    result.set("extensions", extensions)?;
+
+    Ok(result)
+}
+
+fn metadata_table(lua: &Lua, metadata: Metadata) -> LuaResult<LuaTable<'_>> {
+    let result = lua.create_table()?;
+    result.set("size", metadata.size)?;
+    result.set("created", metadata.created)?;
+    result.set("modified", metadata.modified)?;
+    result.set("accessed", metadata.accessed)?;
+    result.set("read_only", metadata.read_only)?;
+    result.set("is_hidden", metadata.is_hidden)?;
+
+    Ok(result)
 }
 
 fn normalize(path: PathBuf) -> PathBuf {
-    let maybe_first_component = path.components().into_iter().next();
+    let maybe_first_component = path.components().next();
     let first_component = match maybe_first_component {
         None => Component::Normal("".as_ref()),
         Some(component) => component
@@ -143,6 +432,48 @@ impl LuaUserData for File {
                 .map_err(external_lua_error)
         });
 
+        methods.add_method("read_json", |lua, this, ()| {
+            let value = this.read_json()
+                .map_err(external_lua_error)?;
+
+            json::to_lua(lua, value)
+        });
+
+        methods.add_method("write_json", |_, this, (value, options): (LuaValue, Option<LuaTable>)| {
+            let (pretty, sort_keys) = match options {
+                Some(options) => (
+                    options.get::<_, Option<bool>>("pretty")?.unwrap_or(false),
+                    options.get::<_, Option<bool>>("sort_keys")?.unwrap_or(false),
+                ),
+                None => (false, false)
+            };
+
+            this.write_json(&json::from_lua(value, sort_keys)?, pretty)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("read_lua_data", |lua, this, ()| {
+            let data = this.read_lua_data()
+                .map_err(external_lua_error)?;
+
+            lua_data::to_lua(lua, data)
+        });
+
+        methods.add_method("write_lua_data", |_, this, (name, value, options): (Option<String>, LuaValue, Option<LuaTable>)| {
+            let mut serialize_options = SerializeOptions::default();
+            if let Some(options) = options {
+                // Indentation is either a number of spaces, or the text to indent with
+                match options.get::<_, LuaValue>("indent")? {
+                    LuaValue::Nil => {}
+                    LuaValue::String(indent) => serialize_options.indent = indent.to_str()?.to_string(),
+                    _ => serialize_options.indent = " ".repeat(options.get::<_, usize>("indent")?),
+                }
+            }
+
+            this.write_lua_data(name.as_deref(), &lua_data::from_lua(value)?, &serialize_options)
+                .map_err(external_lua_error)
+        });
+
         methods.add_method("append_string", |_, this, (content, ): (String, )| {
             this.append_string(content)
                 .map_err(external_lua_error)
@@ -158,7 +489,7 @@ impl LuaUserData for File {
             let normalized_path = path.absolutize()
                 .map_err(external_lua_error)?;
 
-            Ok(this.copy(&normalized_path).map_err(external_lua_error)?)
+            this.copy(&normalized_path).map_err(external_lua_error)
         });
 
         methods.add_method("move", |_, this, (destination, ): (String, )| {
@@ -166,7 +497,7 @@ impl LuaUserData for File {
             let normalized_path = path.absolutize()
                 .map_err(external_lua_error)?;
 
-            Ok(this.move_file(&normalized_path).map_err(external_lua_error)?)
+            this.move_file(&normalized_path).map_err(external_lua_error)
         });
 
         methods.add_method("exists", |_, this, ()| {
@@ -177,6 +508,51 @@ impl LuaUserData for File {
             this.delete()
                 .map_err(external_lua_error)
         });
+
+        methods.add_method("metadata", |lua, this, ()| {
+            let metadata = this.metadata()
+                .map_err(external_lua_error)?;
+
+            metadata_table(lua, metadata)
+        });
+
+        methods.add_method("set_modified", |_, this, (timestamp,): (u64,)| {
+            this.set_modified(timestamp)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("set_read_only", |_, this, (read_only,): (bool,)| {
+            this.set_read_only(read_only)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("hash", |_, this, (algorithm,): (String,)| {
+            let algorithm = algorithm.parse::<HashAlgorithm>()
+                .map_err(external_lua_error)?;
+
+            this.hash(algorithm)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("open", |_, this, (mode,): (Option<String>,)| {
+            let mode = mode.as_deref().unwrap_or("r").parse::<OpenMode>()
+                .map_err(external_lua_error)?;
+
+            this.open(mode)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("lines", |lua, this, ()| {
+            let mut lines = this.lines()
+                .map_err(external_lua_error)?;
+
+            lua.create_function_mut(move |lua, ()| {
+                match lines.next() {
+                    Some(line) => Ok(Some(lua.create_string(&line.map_err(external_lua_error)?)?)),
+                    None => Ok(None)
+                }
+            })
+        });
     }
 }
 
@@ -211,31 +587,89 @@ impl LuaUserData for Directory {
 
         methods.add_method("file", |_, this, (paths,): (Variadic<String>,)| {
             let path: PathBuf = paths.iter().collect();
-            let path = this.path.join(path);
-            let normalized_path = path.absolutize()
-                .map_err(external_lua_error)?;
 
-            file(normalized_path)
+            this.file(path)
                 .map_err(external_lua_error)
         });
 
         methods.add_method("directory", |_, this, (paths,): (Variadic<String>,)| {
             let path: PathBuf = paths.iter().collect();
-            let path = this.path.join(path);
-            let normalized_path = path.absolutize()
+
+            this.directory(path)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("files", |_, this, (key, direction): (Option<String>, Option<String>)| {
+            this.files(sort_order(key, direction)?)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("directories", |_, this, (key, direction): (Option<String>, Option<String>)| {
+            this.directories(sort_order(key, direction)?)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("walk", |lua, this, (options,): (Option<LuaTable>,)| {
+            let options = match options {
+                Some(options) => walk_options(options)?,
+                None => WalkOptions::default()
+            };
+            let entries = this.walk(&options)
+                .map_err(external_lua_error)?;
+
+            entries_table(lua, entries)
+        });
+
+        methods.add_method("glob", |lua, this, (pattern,): (String,)| {
+            let entries = this.glob(pattern)
                 .map_err(external_lua_error)?;
 
-            directory(normalized_path)
+            entries_table(lua, entries)
+        });
+
+        methods.add_method("copy_to", |_, this, (destination, options): (String, Option<LuaTable>)| {
+            let destination = normalize(PathBuf::from(destination));
+            let mut options = transfer_options(options)?;
+
+            this.copy_to(destination, &mut options)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("move_to", |_, this, (destination, options): (String, Option<LuaTable>)| {
+            let destination = normalize(PathBuf::from(destination));
+            let mut options = transfer_options(options)?;
+
+            this.move_to(destination, &mut options)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("stats", |lua, this, (max_depth,): (Option<usize>,)| {
+            let stats = this.stats(max_depth)
+                .map_err(external_lua_error)?;
+
+            stats_table(lua, stats)
+        });
+
+        methods.add_method("snapshot", |_, this, ()| {
+            this.snapshot()
                 .map_err(external_lua_error)
         });
 
-        methods.add_method("files", |_, this, ()| {
-            this.files()
+        methods.add_method("watch", |_, this, (options,): (Option<LuaTable>,)| {
+            let recursive = match options {
+                Some(options) => options.get::<_, Option<bool>>("recursive")?.unwrap_or(true),
+                None => true
+            };
+
+            this.watch(recursive)
                 .map_err(external_lua_error)
         });
 
-        methods.add_method("directories", |_, this, ()| {
-            this.directories()
+        methods.add_method("hash_tree", |_, this, (algorithm,): (Option<String>,)| {
+            let algorithm = algorithm.as_deref().unwrap_or(DEFAULT_HASH_ALGORITHM).parse::<HashAlgorithm>()
+                .map_err(external_lua_error)?;
+
+            this.hash_tree(algorithm)
                 .map_err(external_lua_error)
         });
 
@@ -257,5 +691,162 @@ impl LuaUserData for Directory {
             this.delete()
                 .map_err(external_lua_error)
         });
+
+        methods.add_method("metadata", |lua, this, ()| {
+            let metadata = this.metadata()
+                .map_err(external_lua_error)?;
+
+            metadata_table(lua, metadata)
+        });
+
+        methods.add_method("set_modified", |_, this, (timestamp,): (u64,)| {
+            this.set_modified(timestamp)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("set_read_only", |_, this, (read_only,): (bool,)| {
+            this.set_read_only(read_only)
+                .map_err(external_lua_error)
+        });
+    }
+}
+
+impl LuaUserData for Scope {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("mod_id", |_, this, ()| {
+            Ok(this.mod_id().to_string())
+        });
+
+        methods.add_method("usage", |_, this, ()| {
+            Ok(this.quota().map(|quota| quota.usage()))
+        });
+
+        methods.add_method("remaining", |_, this, ()| {
+            Ok(this.quota().map(|quota| quota.remaining()))
+        });
+
+        methods.add_method("mod_directory", |_, this, ()| {
+            Ok(this.mod_directory())
+        });
+
+        methods.add_method("save_directory", |_, this, ()| {
+            Ok(this.save_directory())
+        });
+
+        methods.add_method("file", |_, this, (paths,): (Variadic<String>,)| {
+            let path: PathBuf = paths.iter().collect();
+
+            this.file(path)
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("directory", |_, this, (paths,): (Variadic<String>,)| {
+            let path: PathBuf = paths.iter().collect();
+
+            this.directory(path)
+                .map_err(external_lua_error)
+        });
+    }
+}
+
+impl LuaUserData for FileHandle {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("read", |lua, this, (count,): (Option<u64>,)| {
+            match this.read(count).map_err(external_lua_error)? {
+                Some(content) => Ok(Some(lua.create_string(&content)?)),
+                None => Ok(None)
+            }
+        });
+
+        methods.add_method_mut("read_line", |lua, this, ()| {
+            match this.read_line().map_err(external_lua_error)? {
+                Some(line) => Ok(Some(lua.create_string(&line)?)),
+                None => Ok(None)
+            }
+        });
+
+        methods.add_method_mut("write", |_, this, (content,): (LuaString,)| {
+            this.write(content.as_bytes())
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method_mut("seek", |_, this, (whence, offset): (Option<String>, Option<i64>)| {
+            let whence = whence.as_deref().unwrap_or("cur").parse::<Whence>()
+                .map_err(external_lua_error)?;
+
+            this.seek(whence, offset.unwrap_or(0))
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method_mut("flush", |_, this, ()| {
+            this.flush()
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method_mut("close", |_, this, ()| {
+            this.close()
+                .map_err(external_lua_error)
+        });
+
+        methods.add_method("is_closed", |_, this, ()| {
+            Ok(this.is_closed())
+        });
+    }
+}
+
+impl LuaUserData for DirectoryWatcher {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("poll", |lua, this, ()| {
+            let changes = this.poll()
+                .map_err(external_lua_error)?;
+
+            let result = lua.create_table()?;
+            for change in changes {
+                let change_table = lua.create_table()?;
+                change_table.set("kind", change.kind.as_str())?;
+                change_table.set("path", crate::util::normalize(change.path))?;
+                change_table.set("destination", change.destination.map(crate::util::normalize))?;
+                result.push(change_table)?;
+            }
+
+            Ok(result)
+        });
+
+        methods.add_method_mut("stop", |_, this, ()| {
+            this.stop();
+            Ok(())
+        });
+    }
+}
+
+impl LuaUserData for Snapshot {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("entries", |lua, this, ()| {
+            let result = lua.create_table()?;
+            for (path, entry) in this.entries() {
+                let entry_table = lua.create_table()?;
+                entry_table.set("is_dir", entry.is_directory)?;
+                entry_table.set("size", entry.size)?;
+                entry_table.set("modified", entry.modified)?;
+                result.set(path.as_str(), entry_table)?;
+            }
+
+            Ok(result)
+        });
+
+        methods.add_method("diff", |lua, this, (newer,): (Snapshot,)| {
+            let diff = this.diff(&newer);
+
+            let result = lua.create_table()?;
+            result.set("added", diff.added)?;
+            result.set("removed", diff.removed)?;
+            result.set("changed", diff.changed)?;
+
+            Ok(result)
+        });
+
+        methods.add_method("serialize", |_, this, ()| {
+            Ok(this.serialize())
+        });
     }
 }
@@ -0,0 +1,144 @@
+use std::fs::{FileTimes, OpenOptions};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Information about a file or a directory, with timestamps in seconds since the Unix epoch.
+/// Timestamps are `None` when not supported by the platform or the filesystem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    /// Size in bytes. Only meaningful for files.
+    pub size: u64,
+    pub created: Option<u64>,
+    pub modified: Option<u64>,
+    pub accessed: Option<u64>,
+    pub read_only: bool,
+    /// Whether the hidden attribute is set on Windows, or the name starts with a dot elsewhere
+    pub is_hidden: bool,
+}
+
+impl Metadata {
+    pub fn of<P: AsRef<Path>>(path: P) -> std::io::Result<Metadata> {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)?;
+
+        Ok(Metadata {
+            size: metadata.len(),
+            created: timestamp(metadata.created()),
+            modified: timestamp(metadata.modified()),
+            accessed: timestamp(metadata.accessed()),
+            read_only: metadata.permissions().readonly(),
+            is_hidden: is_hidden(path, &metadata),
+        })
+    }
+}
+
+fn timestamp(time: std::io::Result<SystemTime>) -> Option<u64> {
+    time.ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+}
+
+#[cfg(windows)]
+fn is_hidden(_: &Path, metadata: &std::fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+    metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn is_hidden(path: &Path, _: &std::fs::Metadata) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// Sets the modification time of a file or a directory, in seconds since the Unix epoch.
+pub(crate) fn set_modified<P: AsRef<Path>>(path: P, timestamp: u64) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        // Only requests the right to change attributes, which read-only files allow as well
+        const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+        // Required to open directories as well as files
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x02000000;
+        options.access_mode(FILE_WRITE_ATTRIBUTES).custom_flags(FILE_FLAG_BACKUP_SEMANTICS);
+    }
+    #[cfg(not(windows))]
+    options.read(true);
+
+    let file = options.open(path)?;
+    file.set_times(FileTimes::new().set_modified(UNIX_EPOCH + Duration::from_secs(timestamp)))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_read_only<P: AsRef<Path>>(path: P, read_only: bool) -> std::io::Result<()> {
+    let mut permissions = std::fs::metadata(&path)?.permissions();
+    permissions.set_readonly(read_only);
+    std::fs::set_permissions(path, permissions)
+}
+
+/// Clears the write permission of everyone when making a file read-only, but only gives it
+/// back to the owner when making it writable, as the standard library would give it to everyone.
+#[cfg(unix)]
+pub(crate) fn set_read_only<P: AsRef<Path>>(path: P, read_only: bool) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    const OWNER_WRITE: u32 = 0o200;
+    const ALL_WRITE: u32 = 0o222;
+
+    let mut permissions = std::fs::metadata(&path)?.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(if read_only { mode & !ALL_WRITE } else { mode | OWNER_WRITE });
+    std::fs::set_permissions(path, permissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::{Metadata, set_modified, set_read_only};
+
+    #[test]
+    fn metadata_should_reflect_changes() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("file.txt");
+        std::fs::write(&path, "qwe").unwrap();
+
+        set_modified(&path, 1_600_000_000).unwrap();
+        set_read_only(&path, true).unwrap();
+        let metadata = Metadata::of(&path).unwrap();
+        set_read_only(&path, false).unwrap();
+
+        assert_eq!(3, metadata.size);
+        assert_eq!(Some(1_600_000_000), metadata.modified);
+        assert!(metadata.read_only);
+        assert!(!metadata.is_hidden);
+        assert!(!Metadata::of(&path).unwrap().read_only);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dot_files_should_be_hidden() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join(".hidden");
+        std::fs::write(&path, "qwe").unwrap();
+
+        assert!(Metadata::of(&path).unwrap().is_hidden);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn clearing_read_only_should_only_make_file_writable_by_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("file.txt");
+        std::fs::write(&path, "qwe").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        set_read_only(&path, true).unwrap();
+        assert_eq!(0o444, std::fs::metadata(&path).unwrap().permissions().mode() & 0o777);
+        set_read_only(&path, false).unwrap();
+
+        assert_eq!(0o644, std::fs::metadata(&path).unwrap().permissions().mode() & 0o777);
+    }
+}
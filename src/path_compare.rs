@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+
+/// How paths are compared against each other when checking whether one contains another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComparisonMode {
+    /// Components have to match exactly, and only the platform's separator is recognized.
+    CaseSensitive,
+    /// Case of components is ignored, and both `\` and `/` are recognized as separators,
+    /// like on NTFS.
+    CaseInsensitive,
+}
+
+impl ComparisonMode {
+    /// Returns the mode matching the filesystem semantics of the platform this was built for.
+    pub fn native() -> ComparisonMode {
+        if cfg!(any(windows, target_os = "macos")) {
+            ComparisonMode::CaseInsensitive
+        } else {
+            ComparisonMode::CaseSensitive
+        }
+    }
+
+    fn components<P: AsRef<Path>>(&self, path: P) -> Vec<String> {
+        match self {
+            ComparisonMode::CaseSensitive => path.as_ref().components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect(),
+            ComparisonMode::CaseInsensitive => ComparisonMode::split_on_any_separator(path).into_iter()
+                .map(|component| component.to_lowercase())
+                .collect()
+        }
+    }
+
+    fn split_on_any_separator<P: AsRef<Path>>(path: P) -> Vec<String> {
+        path.as_ref().to_string_lossy()
+            .replace('\\', "/")
+            .split('/')
+            .enumerate()
+            // Keep the leading empty component of absolute paths, so they don't compare
+            // equal to relative ones
+            .filter(|(index, component)| (*index == 0 || !component.is_empty()) && *component != ".")
+            .map(|(_, component)| component.to_string())
+            .collect()
+    }
+
+    pub fn starts_with<P: AsRef<Path>, Q: AsRef<Path>>(&self, path: P, base: Q) -> bool {
+        self.strip_prefix(path, base).is_some()
+    }
+
+    pub fn equals<P: AsRef<Path>, Q: AsRef<Path>>(&self, path: P, other: Q) -> bool {
+        self.components(path) == self.components(other)
+    }
+
+    /// Returns the remainder of `path` after removing `base` from its beginning,
+    /// or `None` if `path` doesn't start with `base`.
+    pub fn strip_prefix<P: AsRef<Path>, Q: AsRef<Path>>(&self, path: P, base: Q) -> Option<PathBuf> {
+        let path_components = self.components(&path);
+        let base_components = self.components(&base);
+
+        if path_components.len() >= base_components.len()
+            && path_components[..base_components.len()] == base_components[..]
+        {
+            // Return the remainder with its original case, rather than the normalized one
+            let original_components = match self {
+                ComparisonMode::CaseSensitive => self.components(&path),
+                ComparisonMode::CaseInsensitive => ComparisonMode::split_on_any_separator(&path),
+            };
+
+            Some(original_components[base_components.len()..].iter().collect())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::path_compare::ComparisonMode;
+
+    #[test]
+    fn case_insensitive_mode_should_ignore_case_and_separators() {
+        let mode = ComparisonMode::CaseInsensitive;
+
+        assert!(mode.starts_with("c:\\games\\itb\\mods\\a.lua", "C:/Games/ITB"));
+        assert!(mode.starts_with("C:/Games/ITB/", "c:\\games\\itb"));
+        assert!(mode.equals("C:\\Games\\ITB", "c:/games/itb/"));
+        assert!(!mode.starts_with("C:/Games/ITB2", "C:/Games/ITB"));
+    }
+
+    #[test]
+    fn case_sensitive_mode_should_respect_case() {
+        let mode = ComparisonMode::CaseSensitive;
+
+        assert!(mode.starts_with("/games/itb/mods", "/games/itb"));
+        assert!(!mode.starts_with("/games/itb/mods", "/Games/ITB"));
+        assert!(!mode.equals("/games/itb", "/Games/ITB"));
+    }
+
+    #[test]
+    fn strip_prefix_should_keep_original_case_of_remainder() {
+        let mode = ComparisonMode::CaseInsensitive;
+
+        let result = mode.strip_prefix("C:\\Games\\ITB\\Mods\\Config.lua", "c:/games/itb");
+
+        assert_eq!(Some(PathBuf::from("Mods").join("Config.lua")), result);
+    }
+
+    #[test]
+    fn absolute_path_should_not_start_with_relative_path() {
+        let mode = ComparisonMode::CaseInsensitive;
+
+        assert!(!mode.starts_with("/games/itb", "games"));
+    }
+}
@@ -1,69 +1,374 @@
 use std::borrow::Cow;
 use std::io::{Error, ErrorKind};
-use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
 use directories::UserDirs;
 
 use lazy_static::lazy_static;
 use path_absolutize::Absolutize;
 
+use crate::deny_list;
+use crate::path_compare::ComparisonMode;
+
 pub struct PathFilter {}
 
+/// Name of the root for the game's installation directory.
+pub const GAME_ROOT: &str = "game";
+/// Name of the root for the game's save data directory.
+pub const SAVE_DATA_ROOT: &str = "save_data";
+
+/// Upper bound on how many chained symlinks are followed when resolving a path.
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+static CONFIG_LOCK: ConfigLock = ConfigLock::new();
+
 lazy_static! {
     static ref SAVE_DATA_DIR: Mutex<Option<PathBuf>> = Mutex::new(Option::None);
+    static ref REGISTERED_ROOTS: RwLock<Vec<SandboxRoot>> = RwLock::new(Vec::new());
+    /// All roots, so that the built-in ones aren't resolved again for every checked path.
+    /// Cleared whenever the roots change, and always locked before the other state.
+    static ref ROOTS_CACHE: RwLock<Option<Vec<SandboxRoot>>> = RwLock::new(None);
+}
+
+/// One-way switch which prevents the sandbox from being reconfigured once it is engaged.
+pub struct ConfigLock {
+    locked: AtomicBool,
+}
+
+impl ConfigLock {
+    const fn new() -> ConfigLock {
+        ConfigLock {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    pub fn lock(&self) {
+        self.locked.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::SeqCst)
+    }
+
+    /// Fails if the configuration has been locked.
+    pub fn ensure_unlocked(&self) -> std::io::Result<()> {
+        if self.is_locked() {
+            Err(Error::new(ErrorKind::PermissionDenied, "Sandbox configuration is locked and can no longer be changed"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// What Lua is allowed to do with files inside of a root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    ReadOnly,
+    ReadWrite,
+}
+
+/// A named directory which Lua is allowed to access, along with everything inside of it.
+#[derive(Clone, Debug)]
+pub struct SandboxRoot {
+    pub name: String,
+    pub path: PathBuf,
+    pub permission: Permission,
+    resolved_path: PathBuf,
+}
+
+impl SandboxRoot {
+    fn new<S: Into<String>>(name: S, path: PathBuf, permission: Permission) -> std::io::Result<SandboxRoot> {
+        let resolved_path = PathFilter::resolve(&path)?;
+        Ok(SandboxRoot {
+            name: name.into(),
+            path,
+            permission,
+            resolved_path,
+        })
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> std::io::Result<bool> {
+        Ok(ComparisonMode::native().starts_with(PathFilter::resolve(path)?, &self.resolved_path))
+    }
+
+    /// Returns the given path relative to this root, or `None` if it is not within it.
+    pub fn relativize<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Option<PathBuf>> {
+        Ok(ComparisonMode::native().strip_prefix(PathFilter::resolve(path)?, &self.resolved_path))
+    }
 }
 
 impl PathFilter {
     pub fn is_whitelisted<P: AsRef<Path>>(path: P) -> std::io::Result<bool> {
-        let normalized_path = path.as_ref().absolutize()?;
+        let roots = PathFilter::roots_containing(&path)?;
+        if roots.is_empty() {
+            Ok(false)
+        } else {
+            Ok(deny_list::denying_pattern(&roots, &path)?.is_none())
+        }
+    }
+
+    /// Fails unless the given path is within a root, regardless of that root's permission.
+    pub fn check_readable<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+        PathFilter::accessible_root(path).map(|_| ())
+    }
+
+    /// Fails unless the given path is within a root that allows writing.
+    pub fn check_writable<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+        let root = PathFilter::accessible_root(path)?;
+        if root.permission == Permission::ReadWrite {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::PermissionDenied, format!("Path is within the read-only '{}' directory", root.name)))
+        }
+    }
+
+    /// Returns the root containing the given path, failing if there is none or if the path is denied.
+    fn accessible_root<P: AsRef<Path>>(path: P) -> std::io::Result<SandboxRoot> {
+        let roots = PathFilter::roots_containing(&path)?;
+        if roots.is_empty() {
+            return Err(Error::new(ErrorKind::PermissionDenied, "Path is not within an allowed directory"));
+        }
+
+        match deny_list::denying_pattern(&roots, &path)? {
+            Some(pattern) => Err(Error::new(ErrorKind::PermissionDenied, format!("Path is denied by the rule '{}'", pattern))),
+            None => Ok(roots.into_iter().next().unwrap())
+        }
+    }
+
+    /// Returns true if the given path points at one of the roots themselves.
+    pub fn is_root<P: AsRef<Path>>(path: P) -> std::io::Result<bool> {
+        let resolved_path = PathFilter::resolve(path)?;
+
+        Ok(PathFilter::roots()?.iter().any(|root| ComparisonMode::native().equals(&root.resolved_path, &resolved_path)))
+    }
+
+    /// Returns the root containing the given path. When roots are nested, the innermost one wins.
+    pub fn root_of<P: AsRef<Path>>(path: P) -> std::io::Result<Option<SandboxRoot>> {
+        PathFilter::find_root(path, PathFilter::roots()?, ComparisonMode::native())
+    }
+
+    fn find_root<P: AsRef<Path>>(path: P, roots: Vec<SandboxRoot>, mode: ComparisonMode) -> std::io::Result<Option<SandboxRoot>> {
+        Ok(PathFilter::find_roots(path, roots, mode)?.into_iter().next())
+    }
+
+    /// Returns every root containing the given path, innermost first. Deny rules of outer
+    /// roots still apply within roots nested in them.
+    fn roots_containing<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<SandboxRoot>> {
+        PathFilter::find_roots(path, PathFilter::roots()?, ComparisonMode::native())
+    }
+
+    fn find_roots<P: AsRef<Path>>(path: P, roots: Vec<SandboxRoot>, mode: ComparisonMode) -> std::io::Result<Vec<SandboxRoot>> {
+        let resolved_path = PathFilter::resolve(path)?;
+
+        let mut result = roots.into_iter()
+            .filter(|root| mode.starts_with(&resolved_path, &root.resolved_path))
+            .collect::<Vec<_>>();
+        result.sort_by_key(|root| std::cmp::Reverse(root.resolved_path.components().count()));
+
+        Ok(result)
+    }
+
+    /// Returns all roots: the game directory, the save data directory (if it could be located),
+    /// followed by any additionally registered roots in the order they were registered.
+    pub fn roots() -> std::io::Result<Vec<SandboxRoot>> {
+        if let Some(roots) = ROOTS_CACHE.read().unwrap().as_ref() {
+            return Ok(roots.clone());
+        }
+
+        let mut cache = ROOTS_CACHE.write().unwrap();
+        let mut result = vec![SandboxRoot::new(GAME_ROOT, PathFilter::game_directory()?, Permission::ReadOnly)?];
+        let save_data_directory = PathFilter::save_data_directory();
+        if let Ok(save_data_directory) = &save_data_directory {
+            result.push(SandboxRoot::new(SAVE_DATA_ROOT, save_data_directory.clone(), Permission::ReadWrite)?);
+        }
+        result.extend(REGISTERED_ROOTS.read().unwrap().iter().cloned());
+
+        // Otherwise the save data directory is looked for again next time
+        if save_data_directory.is_ok() {
+            *cache = Some(result.clone());
+        }
+        Ok(result)
+    }
 
-        let result = normalized_path.starts_with(PathFilter::game_directory()?)
-            || normalized_path.starts_with(PathFilter::save_data_directory()?);
+    pub fn root<S: AsRef<str>>(name: S) -> std::io::Result<Option<SandboxRoot>> {
+        let result = PathFilter::roots()?.into_iter()
+            .find(|root| root.name == name.as_ref());
 
         Ok(result)
     }
 
+    /// Registers an additional directory as a root, allowing Lua to access it with the given permission.
+    pub fn register_root<S: AsRef<str>, P: AsRef<Path>>(name: S, path: P, permission: Permission) -> std::io::Result<SandboxRoot> {
+        PathFilter::ensure_unlocked()?;
+        let name = name.as_ref();
+        if name.is_empty() {
+            return Err(Error::other("Root name must not be empty"));
+        }
+        if name == GAME_ROOT || name == SAVE_DATA_ROOT {
+            return Err(Error::other(format!("Root name '{}' is reserved", name)));
+        }
+
+        let path = PathFilter::absolutize(path)?;
+        if !path.is_dir() {
+            return Err(Error::other("Root path does not point to an existing directory"));
+        }
+
+        let mut cache = ROOTS_CACHE.write().unwrap();
+        let mut registered_roots = REGISTERED_ROOTS.write().unwrap();
+        if registered_roots.iter().any(|root| root.name == name) {
+            return Err(Error::other(format!("Root '{}' is already registered", name)));
+        }
+
+        let root = SandboxRoot::new(name, path, permission)?;
+        registered_roots.push(root.clone());
+        *cache = None;
+
+        Ok(root)
+    }
+
+    /// Resolves the given path to the location it actually refers to on disk.
+    ///
+    /// Every existing prefix of the path is canonicalized, so symlinks and junctions are
+    /// followed exactly like the OS would follow them when the path is accessed, including
+    /// `..` components that come after a link. Components past the first one that doesn't
+    /// exist yet are resolved lexically.
+    pub(crate) fn resolve<P: AsRef<Path>>(path: P) -> std::io::Result<PathBuf> {
+        PathFilter::resolve_with_depth(path.as_ref(), 0)
+    }
+
+    fn resolve_with_depth(path: &Path, depth: usize) -> std::io::Result<PathBuf> {
+        if depth > MAX_SYMLINK_DEPTH {
+            return Err(Error::other("Too many levels of symbolic links"));
+        }
+
+        let absolute_path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()?.join(path)
+        };
+
+        let mut resolved = PathBuf::new();
+        let mut exists = true;
+        for component in absolute_path.components() {
+            match component {
+                Component::Prefix(_) | Component::RootDir => resolved.push(component),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    // While the path exists, `resolved` is canonical, so popping matches
+                    // what the OS does when it encounters `..`
+                    resolved.pop();
+                }
+                Component::Normal(name) => {
+                    resolved.push(name);
+                    if exists {
+                        match std::fs::canonicalize(&resolved) {
+                            Ok(canonical) => resolved = canonical,
+                            Err(_) => match std::fs::symlink_metadata(&resolved) {
+                                // Dangling link: anything created through it ends up at its target
+                                Ok(metadata) if metadata.file_type().is_symlink() => {
+                                    let target = std::fs::read_link(&resolved)?;
+                                    resolved.pop();
+                                    resolved = PathFilter::resolve_with_depth(&resolved.join(target), depth + 1)?;
+                                }
+                                _ => exists = false
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
     pub fn game_directory() -> std::io::Result<PathBuf> {
-        let cwd = std::env::current_dir()?;
-        let result_cow = cwd.absolutize()?;
+        PathFilter::absolutize(std::env::current_dir()?)
+    }
+
+    pub(crate) fn absolutize<P: AsRef<Path>>(path: P) -> std::io::Result<PathBuf> {
+        let result_cow = path.as_ref().absolutize()?;
         match result_cow {
             Cow::Borrowed(result) => Ok(result.to_path_buf()),
             Cow::Owned(result) => Ok(result)
         }
     }
 
+    /// Overrides the location of the save data directory, instead of looking for it.
+    pub fn set_save_data_directory<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+        PathFilter::ensure_unlocked()?;
+
+        let path = PathFilter::absolutize(path)?;
+        if !path.is_dir() {
+            return Err(Error::other("Save data path does not point to an existing directory"));
+        }
+
+        // Checked again while holding the directory, so it can't change after `lock` pinned it
+        let mut cache = ROOTS_CACHE.write().unwrap();
+        let mut save_data_dir = SAVE_DATA_DIR.lock().unwrap();
+        PathFilter::ensure_unlocked()?;
+        *save_data_dir = Some(path);
+        *cache = None;
+        Ok(())
+    }
+
+    /// Locks the sandbox configuration (roots, the save data directory, deny rules, quotas,
+    /// and so on), so that it can no longer be changed. This cannot be undone.
+    ///
+    /// The save data directory is looked for now if it hasn't been yet, and if it can't be
+    /// found, it remains unavailable afterwards.
+    pub fn lock() {
+        let mut cache = ROOTS_CACHE.write().unwrap();
+        let mut save_data_dir = SAVE_DATA_DIR.lock().unwrap();
+        if save_data_dir.is_none() {
+            *save_data_dir = PathFilter::find_save_data_directory().ok();
+            *cache = None;
+        }
+        CONFIG_LOCK.lock();
+    }
+
+    pub fn is_locked() -> bool {
+        CONFIG_LOCK.is_locked()
+    }
+
+    /// Fails if the sandbox configuration has been locked.
+    pub fn ensure_unlocked() -> std::io::Result<()> {
+        CONFIG_LOCK.ensure_unlocked()
+    }
+
     pub fn save_data_directory() -> std::io::Result<PathBuf> {
         let mut it = SAVE_DATA_DIR.lock().unwrap();
         if it.is_some() {
             Ok(it.as_ref().unwrap().to_path_buf())
+        } else if PathFilter::is_locked() {
+            Err(Error::other("Save data location was not found before the sandbox configuration was locked"))
         } else {
-            if let Some(user_dirs) = UserDirs::new() {
-                let mut candidates = vec![];
+            Ok(it.insert(PathFilter::find_save_data_directory()?).to_path_buf())
+        }
+    }
 
-                // Windows user documents storage
-                if let Some(document_dir) = user_dirs.document_dir() {
-                    candidates.push(document_dir.join("My Games/Into The Breach"));
-                }
+    fn find_save_data_directory() -> std::io::Result<PathBuf> {
+        if let Some(user_dirs) = UserDirs::new() {
+            let mut candidates = vec![];
 
-                // Linux via Steam's Proton wrapper
-                candidates.push(PathBuf::from("./../../steamapps/compatdata/590380/pfx/"));
+            // Windows user documents storage
+            if let Some(document_dir) = user_dirs.document_dir() {
+                candidates.push(document_dir.join("My Games/Into The Breach"));
+            }
 
-                // Installation directory fallback
-                candidates.push(PathBuf::from("./user"));
+            // Linux via Steam's Proton wrapper
+            candidates.push(PathBuf::from("./../../steamapps/compatdata/590380/pfx/"));
 
-                let first_valid_candidate = candidates.into_iter()
-                    .find(|it| PathFilter::is_save_data_location_valid(it))
-                    .ok_or(Error::new(ErrorKind::Other, "Could not find a valid save data location"))?;
-                let save_data_dir_cow = first_valid_candidate.absolutize()?;
-                let save_data_dir = match save_data_dir_cow {
-                    Cow::Borrowed(save_data_dir) => save_data_dir.to_path_buf(),
-                    Cow::Owned(save_dat_dir) => save_dat_dir
-                };
+            // Installation directory fallback
+            candidates.push(PathBuf::from("./user"));
 
-                Ok(it.insert(save_data_dir).to_path_buf())
-            } else {
-                Err(Error::new(ErrorKind::Other, "Couldn't retrieve valid home directory path from the operating system"))
-            }
+            let first_valid_candidate = candidates.into_iter()
+                .find(|it| PathFilter::is_save_data_location_valid(it))
+                .ok_or(Error::other("Could not find a valid save data location"))?;
+
+            PathFilter::absolutize(first_valid_candidate)
+        } else {
+            Err(Error::other("Couldn't retrieve valid home directory path from the operating system"))
         }
     }
 
@@ -75,8 +380,26 @@ impl PathFilter {
 #[cfg(test)]
 mod tests {
     use std::fs::OpenOptions;
+    use std::path::Path;
     use tempfile::tempdir;
-    use crate::path_filter::PathFilter;
+    use crate::path_compare::ComparisonMode;
+    use crate::path_filter::{ConfigLock, GAME_ROOT, PathFilter, Permission, SAVE_DATA_DIR, SAVE_DATA_ROOT, SandboxRoot};
+    use crate::util::temp_root;
+
+    fn is_within<P: AsRef<Path>, Q: AsRef<Path>>(path: P, root: Q) -> bool {
+        let roots = vec![SandboxRoot::new("test", root.as_ref().to_path_buf(), Permission::ReadOnly).unwrap()];
+        PathFilter::find_root(path, roots, ComparisonMode::native()).unwrap().is_some()
+    }
+
+    #[cfg(unix)]
+    fn symlink_dir<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q) {
+        std::os::unix::fs::symlink(original, link).unwrap();
+    }
+
+    #[cfg(windows)]
+    fn symlink_dir<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q) {
+        std::os::windows::fs::symlink_dir(original, link).unwrap();
+    }
 
     #[test]
     fn empty_dir_should_not_be_valid_save_data_location() {
@@ -87,6 +410,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::ineffective_open_options)]
     fn dir_containing_io_test_should_be_valid_save_data_location() {
         let tmp_dir = tempdir().unwrap();
         let tmp_file = OpenOptions::new()
@@ -115,4 +439,206 @@ mod tests {
 
         assert!(result);
     }
+
+    #[test]
+    fn path_inside_root_should_be_whitelisted() {
+        let sandbox = tempdir().unwrap();
+
+        let result = is_within(sandbox.path().join("some/file.txt"), sandbox.path());
+
+        assert!(result);
+    }
+
+    #[test]
+    fn symlink_escaping_root_should_not_be_whitelisted() {
+        let sandbox = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        symlink_dir(outside.path(), sandbox.path().join("link"));
+
+        let result = is_within(sandbox.path().join("link/file.txt"), sandbox.path());
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn symlink_staying_within_root_should_be_whitelisted() {
+        let sandbox = tempdir().unwrap();
+        std::fs::create_dir(sandbox.path().join("real")).unwrap();
+        symlink_dir(sandbox.path().join("real"), sandbox.path().join("link"));
+
+        let result = is_within(sandbox.path().join("link/file.txt"), sandbox.path());
+
+        assert!(result);
+    }
+
+    #[test]
+    fn dangling_symlink_escaping_root_should_not_be_whitelisted() {
+        let sandbox = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        symlink_dir(outside.path().join("missing"), sandbox.path().join("link"));
+
+        let result = is_within(sandbox.path().join("link/file.txt"), sandbox.path());
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn parent_component_after_escaping_symlink_should_not_be_whitelisted() {
+        let sandbox = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        std::fs::create_dir(outside.path().join("nested")).unwrap();
+        symlink_dir(outside.path().join("nested"), sandbox.path().join("link"));
+
+        // Lexically this is `sandbox/file.txt`, but the OS resolves it to `outside/file.txt`
+        let result = is_within(sandbox.path().join("link/../file.txt"), sandbox.path());
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn symlinked_root_should_still_contain_its_children() {
+        let sandbox = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        symlink_dir(outside.path(), sandbox.path().join("link"));
+
+        let result = is_within(outside.path().join("file.txt"), sandbox.path().join("link"));
+
+        assert!(result);
+    }
+
+    #[test]
+    fn innermost_root_should_contain_path_when_roots_are_nested() {
+        let outer = tempdir().unwrap();
+        std::fs::create_dir(outer.path().join("inner")).unwrap();
+        let roots = vec![
+            SandboxRoot::new("outer", outer.path().to_path_buf(), Permission::ReadOnly).unwrap(),
+            SandboxRoot::new("inner", outer.path().join("inner"), Permission::ReadWrite).unwrap(),
+        ];
+
+        let result = PathFilter::find_root(outer.path().join("inner/file.txt"), roots, ComparisonMode::native()).unwrap();
+
+        assert_eq!("inner", result.unwrap().name);
+    }
+
+    #[test]
+    fn registered_root_should_be_whitelisted() {
+        let tmp_dir = tempdir().unwrap();
+        let name = format!("registered_root_test_{}", tmp_dir.path().file_name().unwrap().to_str().unwrap());
+        PathFilter::register_root(&name, tmp_dir.path(), Permission::ReadOnly).unwrap();
+
+        let root = PathFilter::root_of(tmp_dir.path().join("file.txt")).unwrap();
+
+        assert_eq!(name, root.unwrap().name);
+    }
+
+    #[test]
+    fn root_registered_after_listing_roots_should_be_found() {
+        let tmp_dir = tempdir().unwrap();
+        PathFilter::roots().unwrap();
+
+        let name = format!("late_root_test_{}", tmp_dir.path().file_name().unwrap().to_str().unwrap());
+        PathFilter::register_root(&name, tmp_dir.path(), Permission::ReadOnly).unwrap();
+
+        assert!(PathFilter::roots().unwrap().iter().any(|root| root.name == name));
+        assert!(PathFilter::is_whitelisted(tmp_dir.path().join("file.txt")).unwrap());
+    }
+
+    #[test]
+    fn register_root_should_reject_reserved_and_duplicate_names() {
+        let tmp_dir = tempdir().unwrap();
+        let name = format!("duplicate_root_test_{}", tmp_dir.path().file_name().unwrap().to_str().unwrap());
+        PathFilter::register_root(&name, tmp_dir.path(), Permission::ReadOnly).unwrap();
+
+        assert!(PathFilter::register_root(&name, tmp_dir.path(), Permission::ReadOnly).is_err());
+        assert!(PathFilter::register_root(GAME_ROOT, tmp_dir.path(), Permission::ReadOnly).is_err());
+        assert!(PathFilter::register_root(SAVE_DATA_ROOT, tmp_dir.path(), Permission::ReadOnly).is_err());
+    }
+
+    #[test]
+    fn check_writable_should_only_pass_within_read_write_roots() {
+        let read_only = temp_root(Permission::ReadOnly);
+        let read_write = temp_root(Permission::ReadWrite);
+
+        assert!(PathFilter::check_readable(read_only.path().join("file.txt")).is_ok());
+        assert!(PathFilter::check_writable(read_only.path().join("file.txt")).is_err());
+        assert!(PathFilter::check_readable(read_write.path().join("file.txt")).is_ok());
+        assert!(PathFilter::check_writable(read_write.path().join("file.txt")).is_ok());
+    }
+
+    #[test]
+    fn game_directory_should_be_read_only() {
+        let file_path = PathFilter::game_directory().unwrap().join("file.txt");
+
+        assert!(PathFilter::check_readable(&file_path).is_ok());
+        assert!(PathFilter::check_writable(&file_path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn root_should_contain_path_differing_in_case_when_case_insensitive() {
+        let roots = vec![SandboxRoot {
+            name: "game".to_string(),
+            path: std::path::PathBuf::from("/Games/ITB"),
+            permission: Permission::ReadOnly,
+            resolved_path: std::path::PathBuf::from("/Games/ITB"),
+        }];
+
+        let insensitive_result = PathFilter::find_root("/games/itb/mods/config.lua", roots.clone(), ComparisonMode::CaseInsensitive);
+        let sensitive_result = PathFilter::find_root("/games/itb/mods/config.lua", roots, ComparisonMode::CaseSensitive);
+
+        assert!(insensitive_result.unwrap().is_some());
+        assert!(sensitive_result.unwrap().is_none());
+    }
+
+    #[test]
+    fn locked_config_should_refuse_changes() {
+        let config_lock = ConfigLock::new();
+        assert!(config_lock.ensure_unlocked().is_ok());
+
+        config_lock.lock();
+
+        assert!(config_lock.is_locked());
+        assert_eq!(std::io::ErrorKind::PermissionDenied, config_lock.ensure_unlocked().unwrap_err().kind());
+    }
+
+    /// Set for the child process running `locked_config_should_refuse_changes_in_process`.
+    const LOCK_TEST_VARIABLE: &str = "ITB_IO_LOCK_TEST";
+
+    #[test]
+    fn global_lock_should_refuse_changes() {
+        // Locking can't be undone, so it's tested in a child process to not affect other tests
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["path_filter::tests::locked_config_should_refuse_changes_in_process", "--exact", "--ignored"])
+            .env(LOCK_TEST_VARIABLE, "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success() && stdout.contains("1 passed"), "{}", stdout);
+    }
+
+    #[test]
+    #[ignore = "locks the configuration for the whole process, run by global_lock_should_refuse_changes"]
+    fn locked_config_should_refuse_changes_in_process() {
+        if std::env::var_os(LOCK_TEST_VARIABLE).is_none() {
+            return;
+        }
+        let root_dir = temp_root(Permission::ReadWrite);
+        let root_name = PathFilter::root_of(root_dir.path()).unwrap().unwrap().name;
+        let other_dir = tempdir().unwrap();
+        fn is_refused<T>(result: std::io::Result<T>) -> bool {
+            result.is_err_and(|error| error.kind() == std::io::ErrorKind::PermissionDenied)
+        }
+
+        PathFilter::lock();
+
+        assert!(SAVE_DATA_DIR.lock().unwrap().is_some());
+        assert!(is_refused(PathFilter::register_root("other", other_dir.path(), Permission::ReadWrite)));
+        assert!(is_refused(PathFilter::set_save_data_directory(other_dir.path())));
+        assert!(is_refused(crate::deny_list::deny("*.lua", None)));
+        assert!(is_refused(crate::quota::set_root_quota(&root_name, 10)));
+        assert!(is_refused(crate::scope::Scope::new("mod", None, None)));
+        assert!(is_refused(crate::audit::enable(Some(10))));
+        assert!(is_refused(crate::audit::disable()));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::io::Error;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+
+use lazy_static::lazy_static;
+use walkdir::WalkDir;
+
+use crate::path_filter::PathFilter;
+use crate::scope::Scope;
+
+lazy_static! {
+    static ref ROOT_QUOTAS: RwLock<HashMap<String, Arc<Quota>>> = RwLock::new(HashMap::new());
+}
+
+/// Limit on the number of bytes stored inside of a set of directories, along with
+/// the number of bytes currently in use.
+#[derive(Debug)]
+pub struct Quota {
+    name: String,
+    limit: u64,
+    usage: Mutex<u64>,
+}
+
+impl Quota {
+    /// Creates a quota for the given directories, measuring their current usage.
+    pub fn new<S: Into<String>, P: AsRef<Path>>(name: S, limit: u64, directories: &[P]) -> std::io::Result<Quota> {
+        let mut usage = 0;
+        for directory in directories {
+            usage += Quota::measure(directory)?;
+        }
+
+        Ok(Quota {
+            name: name.into(),
+            limit,
+            usage: Mutex::new(usage),
+        })
+    }
+
+    pub(crate) fn measure<P: AsRef<Path>>(directory: P) -> std::io::Result<u64> {
+        if !directory.as_ref().exists() {
+            return Ok(0);
+        }
+
+        let mut result = 0;
+        for entry in WalkDir::new(directory) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                result += entry.metadata()?.len();
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn usage(&self) -> u64 {
+        *self.usage.lock().unwrap()
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.usage())
+    }
+
+    /// Adds `growth` bytes to the usage, failing without changing anything if that would
+    /// exceed the limit. Shrinking (negative growth) always succeeds.
+    fn apply(&self, growth: i64) -> std::io::Result<()> {
+        let mut usage = self.usage.lock().unwrap();
+        if growth < 0 {
+            *usage = usage.saturating_sub(growth.unsigned_abs());
+            return Ok(());
+        }
+
+        let new_usage = usage.saturating_add(growth as u64);
+        if new_usage > self.limit {
+            Err(Error::other(format!(
+                "Quota exceeded for '{}': {} of {} bytes in use, {} more requested",
+                self.name, *usage, self.limit, growth
+            )))
+        } else {
+            *usage = new_usage;
+            Ok(())
+        }
+    }
+
+    /// Adds `growth` bytes to the usage regardless of the limit, for reverting reservations.
+    fn apply_unchecked(&self, growth: i64) {
+        let mut usage = self.usage.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if growth < 0 {
+            *usage = usage.saturating_sub(growth.unsigned_abs());
+        } else {
+            *usage = usage.saturating_add(growth as u64);
+        }
+    }
+}
+
+/// Change in usage that has been accounted for in all applicable quotas. Unless committed,
+/// the change is reverted when this is dropped, so failed operations don't count towards quotas.
+pub(crate) struct Reservation {
+    changes: Vec<(Arc<Quota>, i64)>,
+    committed: bool,
+}
+
+impl Reservation {
+    pub(crate) fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if !self.committed {
+            for (quota, growth) in &self.changes {
+                quota.apply_unchecked(-growth);
+            }
+        }
+    }
+}
+
+/// Returns the size of the file at the given path, or 0 if it doesn't exist.
+pub(crate) fn file_size<P: AsRef<Path>>(path: P) -> i64 {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.len() as i64)
+        .unwrap_or(0)
+}
+
+/// Sets a quota on the root with the given name, replacing any previously set quota.
+pub fn set_root_quota<S: AsRef<str>>(root_name: S, limit: u64) -> std::io::Result<()> {
+    PathFilter::ensure_unlocked()?;
+    let root_name = root_name.as_ref();
+    let root = PathFilter::root(root_name)?
+        .ok_or_else(|| Error::other(format!("No root named '{}' is registered", root_name)))?;
+
+    let quota = Quota::new(root_name, limit, &[root.path])?;
+    ROOT_QUOTAS.write().unwrap().insert(root_name.to_string(), Arc::new(quota));
+
+    Ok(())
+}
+
+pub fn root_quota<S: AsRef<str>>(root_name: S) -> Option<Arc<Quota>> {
+    ROOT_QUOTAS.read().unwrap().get(root_name.as_ref()).cloned()
+}
+
+/// Accounts for `growth` bytes being written to the given path, in the quota of the root
+/// containing it and in the quota of the scope, if any.
+pub(crate) fn reserve<P: AsRef<Path>>(path: P, scope: Option<&Scope>, growth: i64) -> std::io::Result<Reservation> {
+    let changes = quotas_of(path, scope)?.into_iter()
+        .map(|quota| (quota, growth))
+        .collect();
+
+    apply_changes(changes)
+}
+
+/// Accounts for a file of `size` bytes being moved from `source` to `destination`, replacing
+/// `replaced` bytes there. Quotas containing both paths only change by the net difference,
+/// so moves within a nearly full quota don't fail.
+pub(crate) fn reserve_move<P: AsRef<Path>, Q: AsRef<Path>>(
+    source: P,
+    destination: Q,
+    scope: Option<&Scope>,
+    size: i64,
+    replaced: i64,
+) -> std::io::Result<Reservation> {
+    let mut changes: Vec<(Arc<Quota>, i64)> = quotas_of(destination, scope)?.into_iter()
+        .map(|quota| (quota, size - replaced))
+        .collect();
+    for quota in quotas_of(source, scope)? {
+        match changes.iter_mut().find(|(existing, _)| Arc::ptr_eq(existing, &quota)) {
+            Some((_, growth)) => *growth -= size,
+            None => changes.push((quota, -size)),
+        }
+    }
+
+    apply_changes(changes)
+}
+
+/// Returns the quotas of the root containing the given path and of the scope, if any.
+fn quotas_of<P: AsRef<Path>>(path: P, scope: Option<&Scope>) -> std::io::Result<Vec<Arc<Quota>>> {
+    let mut quotas = Vec::new();
+    if let Some(root) = PathFilter::root_of(&path)? {
+        quotas.extend(root_quota(root.name));
+    }
+    if let Some(scope) = scope {
+        if scope.root_of(&path)?.is_some() {
+            quotas.extend(scope.quota());
+        }
+    }
+
+    Ok(quotas)
+}
+
+fn apply_changes(changes: Vec<(Arc<Quota>, i64)>) -> std::io::Result<Reservation> {
+    let mut reservation = Reservation {
+        changes: Vec::new(),
+        committed: false,
+    };
+    for (quota, growth) in changes {
+        // Quotas applied so far get reverted by the reservation's drop if this fails
+        quota.apply(growth)?;
+        reservation.changes.push((quota, growth));
+    }
+
+    Ok(reservation)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::quota::{apply_changes, Quota};
+
+    #[test]
+    fn quota_should_measure_existing_usage() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(tmp_dir.path().join("a.txt"), "qwe").unwrap();
+        std::fs::create_dir(tmp_dir.path().join("nested")).unwrap();
+        std::fs::write(tmp_dir.path().join("nested/b.txt"), "asdf").unwrap();
+
+        let quota = Quota::new("test", 10, &[tmp_dir.path()]).unwrap();
+
+        assert_eq!(7, quota.usage());
+        assert_eq!(3, quota.remaining());
+    }
+
+    #[test]
+    fn growth_beyond_limit_should_fail_without_changing_usage() {
+        let quota = Quota::new::<_, &str>("test", 10, &[]).unwrap();
+
+        quota.apply(6).unwrap();
+        let result = quota.apply(6);
+
+        assert!(result.is_err());
+        assert_eq!(6, quota.usage());
+    }
+
+    #[test]
+    fn reverting_shrinkage_should_succeed_above_limit() {
+        let quota = Arc::new(Quota::new::<_, &str>("test", 10, &[]).unwrap());
+        // Usage can exceed the limit if it was set below the existing usage
+        quota.apply_unchecked(12);
+
+        let reservation = apply_changes(vec![(quota.clone(), -4)]).unwrap();
+        assert_eq!(8, quota.usage());
+        drop(reservation);
+
+        assert_eq!(12, quota.usage());
+    }
+}
@@ -0,0 +1,246 @@
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::directory::Directory;
+use crate::file::File;
+use crate::path_compare::ComparisonMode;
+use crate::path_filter::PathFilter;
+use crate::quota::Quota;
+
+/// Name of the directory inside of save data which holds each mod's own save data folder.
+const MOD_SAVE_DATA_DIRECTORY: &str = "mods";
+/// Name of the directory inside of the game directory in which mods are installed.
+const MOD_INSTALL_DIRECTORY: &str = "mods";
+
+/// A capability confining access to a single mod's own files: its installation folder
+/// and a dedicated folder inside of save data. Both can be read and written, regardless of
+/// the permission of the root they are in, but nothing outside of them can be accessed.
+///
+/// Files and directories obtained through a scope carry it along, so that navigating away
+/// from them (eg. through `parent`) can't escape the scope.
+#[derive(Clone, Debug)]
+pub struct Scope {
+    inner: Arc<ScopeInner>,
+}
+
+#[derive(Debug)]
+struct ScopeInner {
+    mod_id: String,
+    mod_directory: PathBuf,
+    save_directory: PathBuf,
+    resolved_mod_directory: PathBuf,
+    resolved_save_directory: PathBuf,
+    quota: Option<Arc<Quota>>,
+}
+
+impl Scope {
+    /// Creates a scope for the mod with the given id. If `mod_directory` is not specified,
+    /// the mod is assumed to be installed in `mods/<mod_id>` inside of the game directory.
+    /// The mod directory must be within a root, but can't be a root itself.
+    /// If `quota` is specified, the combined size of the mod's directories is limited to
+    /// that many bytes. Scopes can only be created until the configuration is locked.
+    pub fn new<S: AsRef<str>>(mod_id: S, mod_directory: Option<PathBuf>, quota: Option<u64>) -> std::io::Result<Scope> {
+        // Otherwise any mod could create a scope for another mod after the mod loader is done
+        PathFilter::ensure_unlocked()?;
+        let mod_id = mod_id.as_ref();
+        if !Scope::is_mod_id_valid(mod_id) {
+            return Err(Error::other(format!("'{}' is not a valid mod id", mod_id)));
+        }
+
+        let mod_directory = match mod_directory {
+            Some(mod_directory) => PathFilter::absolutize(mod_directory)?,
+            None => PathFilter::game_directory()?.join(MOD_INSTALL_DIRECTORY).join(mod_id)
+        };
+        if !PathFilter::is_whitelisted(&mod_directory)? {
+            return Err(Error::other("Mod directory is not within an allowed directory"));
+        }
+        // Otherwise a mod could give itself write access to a whole root, like the game directory
+        if PathFilter::is_root(&mod_directory)? {
+            return Err(Error::other("Mod directory cannot be a root directory itself"));
+        }
+
+        let save_directory = PathFilter::save_data_directory()?
+            .join(MOD_SAVE_DATA_DIRECTORY)
+            .join(mod_id);
+
+        let quota = match quota {
+            Some(limit) => Some(Arc::new(Quota::new(mod_id, limit, &[&mod_directory, &save_directory])?)),
+            None => None
+        };
+
+        Ok(Scope {
+            inner: Arc::new(ScopeInner {
+                mod_id: mod_id.to_string(),
+                resolved_mod_directory: PathFilter::resolve(&mod_directory)?,
+                resolved_save_directory: PathFilter::resolve(&save_directory)?,
+                mod_directory,
+                save_directory,
+                quota,
+            })
+        })
+    }
+
+    fn is_mod_id_valid(mod_id: &str) -> bool {
+        !mod_id.is_empty()
+            && mod_id != "."
+            && mod_id != ".."
+            && mod_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    }
+
+    pub fn mod_id(&self) -> &str {
+        &self.inner.mod_id
+    }
+
+    pub fn quota(&self) -> Option<Arc<Quota>> {
+        self.inner.quota.clone()
+    }
+
+    pub fn mod_directory(&self) -> Directory {
+        Directory::from(&self.inner.mod_directory).with_scope(Some(self.clone()))
+    }
+
+    pub fn save_directory(&self) -> Directory {
+        Directory::from(&self.inner.save_directory).with_scope(Some(self.clone()))
+    }
+
+    /// Returns a file at the given path. Relative paths are resolved against the mod's directory.
+    pub fn file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<File> {
+        self.mod_directory().file(path)
+    }
+
+    /// Returns a directory at the given path. Relative paths are resolved against the mod's directory.
+    pub fn directory<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Directory> {
+        self.mod_directory().directory(path)
+    }
+
+    /// Returns the scope's directory (either the mod's directory, or its save data directory)
+    /// which contains the given path.
+    pub fn root_of<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Option<Directory>> {
+        let resolved_path = PathFilter::resolve(path)?;
+
+        let mode = ComparisonMode::native();
+        let result = if mode.starts_with(&resolved_path, &self.inner.resolved_mod_directory) {
+            Some(self.mod_directory())
+        } else if mode.starts_with(&resolved_path, &self.inner.resolved_save_directory) {
+            Some(self.save_directory())
+        } else {
+            None
+        };
+
+        Ok(result)
+    }
+
+    /// Returns true if the given path points at one of the scope's directories themselves.
+    pub fn is_root<P: AsRef<Path>>(&self, path: P) -> std::io::Result<bool> {
+        let resolved_path = PathFilter::resolve(path)?;
+
+        let mode = ComparisonMode::native();
+        Ok(mode.equals(&resolved_path, &self.inner.resolved_mod_directory)
+            || mode.equals(&resolved_path, &self.inner.resolved_save_directory))
+    }
+
+    pub fn is_whitelisted<P: AsRef<Path>>(&self, path: P) -> std::io::Result<bool> {
+        Ok(self.root_of(&path)?.is_some() && PathFilter::is_whitelisted(&path)?)
+    }
+
+    pub fn check_readable<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        PathFilter::check_readable(&path)?;
+        if self.root_of(&path)?.is_some() {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::PermissionDenied, format!("Path is outside of the scope of mod '{}'", self.mod_id())))
+        }
+    }
+
+    /// Scopes grant write access to their own directories, so this only needs to
+    /// check that the path is within the scope.
+    pub fn check_writable<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        self.check_readable(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::file::File;
+    use crate::path_filter::Permission;
+    use crate::scope::Scope;
+    use crate::util::temp_root;
+
+    #[test]
+    fn invalid_mod_ids_should_be_rejected() {
+        assert!(Scope::new("", None, None).is_err());
+        assert!(Scope::new("..", None, None).is_err());
+        assert!(Scope::new("../other_mod", None, None).is_err());
+        assert!(Scope::new("some/mod", None, None).is_err());
+    }
+
+    #[test]
+    fn mod_directory_should_not_be_a_root() {
+        let mods_dir = temp_root(Permission::ReadOnly);
+
+        assert!(Scope::new("mod_a", Some(mods_dir.path().to_path_buf()), None).is_err());
+        assert!(Scope::new("mod_a", Some(PathBuf::from(".")), None).is_err());
+    }
+
+    #[test]
+    fn scope_should_not_allow_access_to_other_mods() {
+        let mods_dir = temp_root(Permission::ReadOnly);
+        std::fs::create_dir(mods_dir.path().join("mod_a")).unwrap();
+        std::fs::create_dir(mods_dir.path().join("mod_b")).unwrap();
+        let scope = Scope::new("mod_a", Some(mods_dir.path().join("mod_a")), None).unwrap();
+
+        assert!(scope.file("config.lua").is_ok());
+        assert!(scope.file("../mod_b/config.lua").is_err());
+        assert!(scope.directory("..").is_err());
+
+        let file = scope.file("config.lua").unwrap();
+        file.write_string("qwe").unwrap();
+        assert_eq!("qwe", file.read_to_string().unwrap());
+        assert!(file.copy(&mods_dir.path().join("mod_b/config.lua")).is_err());
+        assert!(!mods_dir.path().join("mod_b/config.lua").exists());
+    }
+
+    #[test]
+    fn navigating_up_should_not_escape_scope() {
+        let mods_dir = temp_root(Permission::ReadOnly);
+        std::fs::create_dir(mods_dir.path().join("mod_a")).unwrap();
+        let scope = Scope::new("mod_a", Some(mods_dir.path().join("mod_a")), None).unwrap();
+
+        let file = scope.file("config.lua").unwrap();
+        let parent = file.parent().unwrap().unwrap();
+
+        assert!(parent.parent().is_err());
+        assert!(parent.delete().is_err());
+    }
+
+    #[test]
+    fn writes_beyond_scope_quota_should_be_refused() {
+        let mods_dir = temp_root(Permission::ReadOnly);
+        std::fs::create_dir(mods_dir.path().join("mod_a")).unwrap();
+        let scope = Scope::new("mod_a", Some(mods_dir.path().join("mod_a")), Some(10)).unwrap();
+        let file = scope.file("log.txt").unwrap();
+
+        file.append_string("123456").unwrap();
+        let result = file.append_string("123456");
+
+        assert!(result.is_err());
+        assert_eq!("123456", file.read_to_string().unwrap());
+        assert_eq!(4, scope.quota().unwrap().remaining());
+
+        file.delete().unwrap();
+        assert_eq!(10, scope.quota().unwrap().remaining());
+    }
+
+    #[test]
+    fn unscoped_file_in_mod_directory_should_stay_read_only() {
+        let mods_dir = temp_root(Permission::ReadOnly);
+        std::fs::create_dir(mods_dir.path().join("mod_a")).unwrap();
+
+        let file = File::from(mods_dir.path().join("mod_a/config.lua"));
+
+        assert!(file.write_string("qwe").is_err());
+    }
+}
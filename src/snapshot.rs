@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+/// First line of serialized snapshots, identifying the format and its version.
+const SNAPSHOT_HEADER: &str = "itb_io snapshot 1";
+
+/// State of a single file or directory at the time a snapshot was taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    pub is_directory: bool,
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub modified: Option<u64>,
+}
+
+/// State of the contents of a directory, keyed by paths relative to it, with `/` as the separator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    entries: BTreeMap<String, SnapshotEntry>,
+}
+
+/// Differences between two snapshots of the same directory, each sorted by path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl Snapshot {
+    pub(crate) fn insert(&mut self, relative_path: String, entry: SnapshotEntry) {
+        self.entries.insert(relative_path, entry);
+    }
+
+    pub fn entries(&self) -> &BTreeMap<String, SnapshotEntry> {
+        &self.entries
+    }
+
+    /// Returns what changed between this snapshot and a newer one. Files count as changed when
+    /// their size or modification time differ. Directories only count as changed when they
+    /// turned into files, since their own size and modification time reflect changes to their
+    /// contents, which are reported separately.
+    pub fn diff(&self, newer: &Snapshot) -> SnapshotDiff {
+        let mut result = SnapshotDiff::default();
+
+        for (path, entry) in &self.entries {
+            match newer.entries.get(path) {
+                None => result.removed.push(path.clone()),
+                Some(newer_entry) => {
+                    let is_changed = entry.is_directory != newer_entry.is_directory
+                        || (!entry.is_directory && entry != newer_entry);
+                    if is_changed {
+                        result.changed.push(path.clone());
+                    }
+                }
+            }
+        }
+
+        result.added = newer.entries.keys()
+            .filter(|path| !self.entries.contains_key(*path))
+            .cloned()
+            .collect();
+
+        result
+    }
+
+    /// Serializes the snapshot into lines of tab-separated values, which can be read back
+    /// with `parse`.
+    pub fn serialize(&self) -> String {
+        let mut result = String::from(SNAPSHOT_HEADER);
+        result.push('\n');
+
+        for (path, entry) in &self.entries {
+            result.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                if entry.is_directory { "d" } else { "f" },
+                entry.size,
+                entry.modified.map(|modified| modified.to_string()).unwrap_or_else(|| "-".to_string()),
+                path,
+            ));
+        }
+
+        result
+    }
+
+    pub fn parse<S: AsRef<str>>(text: S) -> std::io::Result<Snapshot> {
+        let mut lines = text.as_ref().lines();
+        if lines.next() != Some(SNAPSHOT_HEADER) {
+            return Err(Error::new(ErrorKind::InvalidData, "Text is not a serialized snapshot"));
+        }
+
+        let mut result = Snapshot::default();
+        for (index, line) in lines.enumerate() {
+            let invalid_line = || Error::new(ErrorKind::InvalidData, format!("Invalid snapshot entry on line {}", index + 2));
+
+            // Path comes last, so that it may contain tabs itself
+            let mut fields = line.splitn(4, '\t');
+            let is_directory = match fields.next() {
+                Some("d") => true,
+                Some("f") => false,
+                _ => return Err(invalid_line())
+            };
+            let size = fields.next()
+                .and_then(|size| size.parse().ok())
+                .ok_or_else(invalid_line)?;
+            let modified = match fields.next() {
+                Some("-") => None,
+                Some(modified) => Some(modified.parse().map_err(|_| invalid_line())?),
+                None => return Err(invalid_line())
+            };
+            let path = fields.next().ok_or_else(invalid_line)?;
+
+            result.insert(path.to_string(), SnapshotEntry { is_directory, size, modified });
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::snapshot::{Snapshot, SnapshotEntry};
+
+    fn file(size: u64, modified: u64) -> SnapshotEntry {
+        SnapshotEntry { is_directory: false, size, modified: Some(modified) }
+    }
+
+    fn directory(modified: u64) -> SnapshotEntry {
+        SnapshotEntry { is_directory: true, size: 4096, modified: Some(modified) }
+    }
+
+    #[test]
+    fn diff_should_report_added_removed_and_changed_files() {
+        let mut older = Snapshot::default();
+        older.insert("img".to_string(), directory(1));
+        older.insert("img/unit.png".to_string(), file(10, 1));
+        older.insert("init.lua".to_string(), file(20, 1));
+        older.insert("old.lua".to_string(), file(30, 1));
+        let mut newer = Snapshot::default();
+        newer.insert("img".to_string(), directory(2));
+        newer.insert("img/unit.png".to_string(), file(10, 2));
+        newer.insert("init.lua".to_string(), file(20, 1));
+        newer.insert("new.lua".to_string(), file(30, 2));
+
+        let diff = older.diff(&newer);
+
+        assert_eq!(vec!["new.lua"], diff.added);
+        assert_eq!(vec!["old.lua"], diff.removed);
+        assert_eq!(vec!["img/unit.png"], diff.changed);
+    }
+
+    #[test]
+    fn snapshot_should_be_parsed_back_as_serialized() {
+        let mut snapshot = Snapshot::default();
+        snapshot.insert("img".to_string(), directory(1));
+        snapshot.insert("img/with\ttab.png".to_string(), file(10, 2));
+        snapshot.insert("init.lua".to_string(), SnapshotEntry { is_directory: false, size: 3, modified: None });
+
+        let result = Snapshot::parse(snapshot.serialize()).unwrap();
+
+        assert_eq!(snapshot, result);
+        assert!(Snapshot::parse("f\t1\t1\tinit.lua").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use std::cmp::Ordering;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Property directory entries are sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// Names compared case-insensitively, character by character
+    Name,
+    /// Names compared case-insensitively, with runs of digits compared by their numeric value,
+    /// so that `mod2` comes before `mod10`
+    Natural,
+    Modified,
+    Size,
+}
+
+impl FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(SortKey::Name),
+            "natural" => Ok(SortKey::Natural),
+            "modified" => Ok(SortKey::Modified),
+            "size" => Ok(SortKey::Size),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Invalid sort key '{}'", s)))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl FromStr for SortDirection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(SortDirection::Ascending),
+            "desc" => Ok(SortDirection::Descending),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Invalid sort direction '{}'", s)))
+        }
+    }
+}
+
+/// Order of directory entries. Entries which compare equal by the key are ordered by their
+/// exact names, so that the order is the same on every machine. Defaults to ascending by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub direction: SortDirection,
+}
+
+impl SortOrder {
+    pub fn new(key: SortKey, direction: SortDirection) -> SortOrder {
+        SortOrder { key, direction }
+    }
+
+    /// Returns the value the entry at the given path is sorted by. Reads metadata if the key
+    /// requires it, so values should be computed once per entry, rather than per comparison.
+    pub(crate) fn value(&self, path: &Path) -> SortValue {
+        let name = file_name(path);
+        let primary = match self.key {
+            SortKey::Name => PrimaryValue::Name(name.to_lowercase()),
+            SortKey::Natural => PrimaryValue::Natural(NaturalName(name.clone())),
+            SortKey::Modified => PrimaryValue::Modified(modified(path)),
+            SortKey::Size => PrimaryValue::Size(size(path)),
+        };
+
+        SortValue { primary, name }
+    }
+
+    pub(crate) fn compare_values(&self, a: &SortValue, b: &SortValue) -> Ordering {
+        match self.direction {
+            SortDirection::Ascending => a.cmp(b),
+            SortDirection::Descending => b.cmp(a),
+        }
+    }
+
+    /// Sorts entries by the paths returned for them, computing every entry's value only once.
+    pub(crate) fn sort<T, F: Fn(&T) -> &Path>(&self, entries: &mut [T], path: F) {
+        entries.sort_by_cached_key(|entry| self.value(path(entry)));
+        if self.direction == SortDirection::Descending {
+            entries.reverse();
+        }
+    }
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        SortOrder::new(SortKey::Name, SortDirection::Ascending)
+    }
+}
+
+/// Value of an entry for a sort order. Entries which compare equal by the key are ordered by
+/// their exact names.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct SortValue {
+    primary: PrimaryValue,
+    name: String,
+}
+
+/// Value of an entry for a sort key. Only values for the same key are ever compared.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum PrimaryValue {
+    Name(String),
+    Natural(NaturalName),
+    Modified(Option<std::time::SystemTime>),
+    Size(u64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct NaturalName(String);
+
+impl Ord for NaturalName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_natural(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for NaturalName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+}
+
+/// Compares names case-insensitively, treating runs of digits as numbers.
+fn compare_natural(a: &str, b: &str) -> Ordering {
+    let mut chunks_a = natural_chunks(a);
+    let mut chunks_b = natural_chunks(b);
+
+    loop {
+        let result = match (chunks_a.next(), chunks_b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(chunk_a), Some(chunk_b)) => {
+                let is_number_a = chunk_a.starts_with(|c: char| c.is_ascii_digit());
+                let is_number_b = chunk_b.starts_with(|c: char| c.is_ascii_digit());
+                if is_number_a && is_number_b {
+                    // Compare arbitrarily long numbers without parsing them, by their length
+                    // once leading zeros are ignored, and then digit by digit
+                    let digits_a = chunk_a.trim_start_matches('0');
+                    let digits_b = chunk_b.trim_start_matches('0');
+                    digits_a.len().cmp(&digits_b.len())
+                        .then_with(|| digits_a.cmp(digits_b))
+                } else {
+                    chunk_a.to_lowercase().cmp(&chunk_b.to_lowercase())
+                }
+            }
+        };
+
+        if result != Ordering::Equal {
+            return result;
+        }
+    }
+}
+
+/// Splits a name into alternating runs of digits and non-digits.
+fn natural_chunks(name: &str) -> impl Iterator<Item = &str> {
+    let mut remaining = name;
+    std::iter::from_fn(move || {
+        let first = remaining.chars().next()?;
+        let end = remaining.find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(remaining.len());
+        let (chunk, rest) = remaining.split_at(end);
+        remaining = rest;
+        Some(chunk)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::sort::{SortDirection, SortKey, SortOrder};
+
+    fn sorted(names: &[&str], order: SortOrder) -> Vec<String> {
+        let mut result = names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        order.sort(&mut result, |name| Path::new(name));
+        result
+    }
+
+    #[test]
+    fn natural_sort_should_compare_numbers_by_value() {
+        let order = SortOrder::new(SortKey::Natural, SortDirection::Ascending);
+
+        assert_eq!(
+            vec!["mod1", "mod2", "mod02b", "Mod10", "mod10a", "mod100"],
+            sorted(&["mod100", "Mod10", "mod2", "mod10a", "mod1", "mod02b"], order)
+        );
+    }
+
+    #[test]
+    fn name_sort_should_ignore_case_but_stay_deterministic() {
+        let order = SortOrder::default();
+
+        assert_eq!(vec!["a", "B", "b", "c", "mod10", "mod2"], sorted(&["c", "mod2", "b", "mod10", "B", "a"], order));
+    }
+
+    #[test]
+    fn descending_order_should_reverse_ascending() {
+        let order = SortOrder::new(SortKey::Natural, SortDirection::Descending);
+
+        assert_eq!(vec!["mod10", "mod2", "mod1"], sorted(&["mod2", "mod10", "mod1"], order));
+    }
+
+    #[test]
+    fn size_sort_should_fall_back_to_names() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        for (name, content) in [("b.txt", "12"), ("a.txt", "12"), ("c.txt", "1")] {
+            std::fs::write(tmp_dir.path().join(name), content).unwrap();
+        }
+        let mut paths = ["b.txt", "c.txt", "a.txt"].map(|name| tmp_dir.path().join(name));
+
+        SortOrder::new(SortKey::Size, SortDirection::Ascending).sort(&mut paths, |path| path);
+
+        assert_eq!(["c.txt", "a.txt", "b.txt"].map(|name| tmp_dir.path().join(name)), paths);
+    }
+}
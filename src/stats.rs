@@ -0,0 +1,89 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+/// Number of largest files kept track of while gathering statistics.
+pub const LARGEST_FILE_COUNT: usize = 10;
+
+/// Totals for all files sharing an extension.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtensionStats {
+    pub bytes: u64,
+    pub file_count: u64,
+}
+
+/// Summary of the space used by the contents of a directory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DirectoryStats {
+    pub total_bytes: u64,
+    pub file_count: u64,
+    pub directory_count: u64,
+    /// Paths relative to the directory and sizes of the largest files, largest first
+    pub largest_files: Vec<(String, u64)>,
+    /// Totals keyed by lowercase extension, with files without one under an empty string
+    pub extensions: BTreeMap<String, ExtensionStats>,
+}
+
+/// Accumulates statistics about entries as they are visited.
+#[derive(Debug, Default)]
+pub(crate) struct StatsCollector {
+    stats: DirectoryStats,
+    // Min-heap of the largest files seen so far, so that the smallest of them can be evicted
+    largest_files: BinaryHeap<Reverse<(u64, String)>>,
+}
+
+impl StatsCollector {
+    pub(crate) fn add_file(&mut self, relative_path: String, extension: Option<String>, size: u64) {
+        self.stats.total_bytes += size;
+        self.stats.file_count += 1;
+
+        let extension_stats = self.stats.extensions
+            .entry(extension.unwrap_or_default().to_lowercase())
+            .or_default();
+        extension_stats.bytes += size;
+        extension_stats.file_count += 1;
+
+        self.largest_files.push(Reverse((size, relative_path)));
+        if self.largest_files.len() > LARGEST_FILE_COUNT {
+            self.largest_files.pop();
+        }
+    }
+
+    pub(crate) fn add_directory(&mut self) {
+        self.stats.directory_count += 1;
+    }
+
+    pub(crate) fn finish(mut self) -> DirectoryStats {
+        // Sorting the reversed entries ascending orders them by descending size
+        self.stats.largest_files = self.largest_files.into_sorted_vec().into_iter()
+            .map(|Reverse((size, path))| (path, size))
+            .collect();
+
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{LARGEST_FILE_COUNT, StatsCollector};
+
+    #[test]
+    fn collector_should_keep_only_largest_files() {
+        let mut collector = StatsCollector::default();
+        for size in 0..20 {
+            collector.add_file(format!("file{}.dat", size), Some("DAT".to_string()), size);
+        }
+        collector.add_file("README".to_string(), None, 5);
+        collector.add_directory();
+
+        let stats = collector.finish();
+
+        assert_eq!(195, stats.total_bytes);
+        assert_eq!(21, stats.file_count);
+        assert_eq!(1, stats.directory_count);
+        assert_eq!(LARGEST_FILE_COUNT, stats.largest_files.len());
+        assert_eq!(("file19.dat".to_string(), 19), stats.largest_files[0]);
+        assert_eq!(("file10.dat".to_string(), 10), stats.largest_files[9]);
+        assert_eq!(20, stats.extensions["dat"].file_count);
+        assert_eq!(5, stats.extensions[""].bytes);
+    }
+}
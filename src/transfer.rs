@@ -0,0 +1,52 @@
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use globset::GlobMatcher;
+
+/// What to do when a file being copied or moved already exists at its destination.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Replace the existing file
+    Overwrite,
+    /// Leave the existing file, and don't transfer the source file
+    Skip,
+    /// Don't transfer anything if any of the files already exists
+    Fail,
+}
+
+impl FromStr for OverwritePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overwrite" => Ok(OverwritePolicy::Overwrite),
+            "skip" => Ok(OverwritePolicy::Skip),
+            "fail" => Ok(OverwritePolicy::Fail),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("Invalid overwrite policy '{}'", s)))
+        }
+    }
+}
+
+/// Called after every file with its path relative to the source directory, the number of files
+/// processed so far, and the total number of files to process, including skipped ones.
+/// Returning an error aborts the transfer.
+pub type ProgressCallback<'a> = Box<dyn FnMut(&str, usize, usize) -> std::io::Result<()> + 'a>;
+
+/// Options for copying or moving a directory.
+pub struct TransferOptions<'a> {
+    pub overwrite: OverwritePolicy,
+    /// Entries whose path relative to the source directory matches this are not transferred,
+    /// along with their contents
+    pub skip: Option<GlobMatcher>,
+    pub progress: Option<ProgressCallback<'a>>,
+}
+
+impl Default for TransferOptions<'_> {
+    fn default() -> Self {
+        TransferOptions {
+            overwrite: OverwritePolicy::Fail,
+            skip: None,
+            progress: None,
+        }
+    }
+}
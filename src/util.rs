@@ -3,3 +3,13 @@ use std::path::Path;
 pub(crate) fn normalize<P: AsRef<Path>>(path: P) -> String {
     path.as_ref().to_str().unwrap().to_string().replace("\\", "/")
 }
+
+/// Creates a temporary directory and registers it as a root with the given permission.
+#[cfg(test)]
+pub(crate) fn temp_root(permission: crate::path_filter::Permission) -> tempfile::TempDir {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let name = format!("temp_root_{}", tmp_dir.path().file_name().unwrap().to_str().unwrap());
+    crate::path_filter::PathFilter::register_root(name, tmp_dir.path(), permission).unwrap();
+
+    tmp_dir
+}
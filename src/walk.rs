@@ -0,0 +1,59 @@
+use std::io::{Error, ErrorKind};
+
+use globset::{GlobBuilder, GlobMatcher};
+
+use crate::directory::Directory;
+use crate::file::File;
+use crate::path_compare::ComparisonMode;
+use crate::sort::SortOrder;
+
+/// Options for recursively walking a directory.
+#[derive(Debug)]
+pub struct WalkOptions {
+    /// Maximum depth to descend to, where 1 means only the direct children.
+    /// Unlimited if `None`.
+    pub max_depth: Option<usize>,
+    /// Only entries whose path relative to the walked directory matches this are returned
+    pub include: Option<GlobMatcher>,
+    /// Entries whose path relative to the walked directory matches this are neither returned,
+    /// nor descended into
+    pub exclude: Option<GlobMatcher>,
+    pub files: bool,
+    pub directories: bool,
+    /// Order the entries of every directory are visited in, depth-first
+    pub order: SortOrder,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            max_depth: None,
+            include: None,
+            exclude: None,
+            files: true,
+            directories: true,
+            order: SortOrder::default(),
+        }
+    }
+}
+
+/// File or directory found while walking a directory.
+#[derive(Debug)]
+pub enum DirectoryEntry {
+    File(File),
+    Directory(Directory),
+}
+
+/// Compiles a glob pattern matched against paths relative to a directory, with `/` as the
+/// separator. `*` doesn't match across directories, while `**` does. Case is ignored on
+/// platforms with case-insensitive filesystems.
+pub(crate) fn compile_glob<S: AsRef<str>>(pattern: S) -> std::io::Result<GlobMatcher> {
+    let pattern = pattern.as_ref();
+    let glob = GlobBuilder::new(pattern)
+        .case_insensitive(ComparisonMode::native() == ComparisonMode::CaseInsensitive)
+        .literal_separator(true)
+        .build()
+        .map_err(|error| Error::new(ErrorKind::InvalidInput, format!("Invalid glob pattern '{}': {}", pattern, error)))?;
+
+    Ok(glob.compile_matcher())
+}
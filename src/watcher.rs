@@ -0,0 +1,174 @@
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, sync_channel};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::event::{ModifyKind, RenameMode};
+
+use crate::path_filter::PathFilter;
+use crate::scope::Scope;
+
+/// Number of events kept while waiting to be polled. Further events are dropped until
+/// the queue is drained.
+const MAX_PENDING_EVENTS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+    Renamed,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Renamed => "renamed",
+        }
+    }
+}
+
+/// Change of a single file or directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub path: PathBuf,
+    /// New path of a renamed entry, when the platform reports both paths at once
+    pub destination: Option<PathBuf>,
+}
+
+/// Converts an event reported by the platform into changes, ignoring kinds of events
+/// which don't change anything, like accessing a file.
+fn change_events(event: Event) -> Vec<ChangeEvent> {
+    let kind = match event.kind {
+        EventKind::Create(_) => ChangeKind::Created,
+        EventKind::Modify(ModifyKind::Name(_)) => ChangeKind::Renamed,
+        EventKind::Modify(_) => ChangeKind::Modified,
+        EventKind::Remove(_) => ChangeKind::Removed,
+        _ => return Vec::new()
+    };
+
+    match (event.kind, event.paths.as_slice()) {
+        (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => vec![ChangeEvent {
+            kind,
+            path: from.clone(),
+            destination: Some(to.clone()),
+        }],
+        (_, paths) => paths.iter()
+            .map(|path| ChangeEvent { kind, path: path.clone(), destination: None })
+            .collect()
+    }
+}
+
+/// Watches a directory for changes on a background thread, queueing them until polled.
+pub struct DirectoryWatcher {
+    // Events stop being reported once the watcher is dropped
+    watcher: Option<RecommendedWatcher>,
+    events: Receiver<notify::Result<Event>>,
+    scope: Option<Scope>,
+}
+
+impl DirectoryWatcher {
+    /// Starts watching the given directory. Callers are responsible for checking access beforehand.
+    pub(crate) fn new<P: AsRef<Path>>(path: P, recursive: bool, scope: Option<Scope>) -> std::io::Result<DirectoryWatcher> {
+        let (sender, receiver) = sync_channel(MAX_PENDING_EVENTS);
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // Drop events rather than blocking the background thread when the queue is full
+            let _ = sender.try_send(event);
+        }).map_err(Error::other)?;
+
+        let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        watcher.watch(path.as_ref(), mode)
+            .map_err(Error::other)?;
+
+        Ok(DirectoryWatcher {
+            watcher: Some(watcher),
+            events: receiver,
+            scope,
+        })
+    }
+
+    /// Returns the changes since the last poll, oldest first, leaving out any to paths
+    /// which are not whitelisted.
+    pub fn poll(&self) -> std::io::Result<Vec<ChangeEvent>> {
+        let mut result = Vec::new();
+        // Errors only mean some events were missed, which can't be helped at this point
+        for event in self.events.try_iter().flatten() {
+            for change in change_events(event) {
+                if self.is_whitelisted(&change.path)?
+                    && change.destination.as_ref().map_or(Ok(true), |destination| self.is_whitelisted(destination))?
+                {
+                    result.push(change);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Stops watching. Changes queued until now can still be polled.
+    pub fn stop(&mut self) {
+        self.watcher = None;
+    }
+
+    fn is_whitelisted<P: AsRef<Path>>(&self, path: P) -> std::io::Result<bool> {
+        match &self.scope {
+            Some(scope) => scope.is_whitelisted(path),
+            None => PathFilter::is_whitelisted(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    use notify::{Event, EventKind};
+    use notify::event::{AccessKind, CreateKind, ModifyKind, RenameMode};
+
+    use crate::directory::Directory;
+    use crate::path_filter::Permission;
+    use crate::util::temp_root;
+    use crate::watcher::{change_events, ChangeEvent, ChangeKind};
+
+    #[test]
+    fn renames_should_be_reported_with_destination() {
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(PathBuf::from("a.lua"))
+            .add_path(PathBuf::from("b.lua"));
+
+        assert_eq!(
+            vec![ChangeEvent { kind: ChangeKind::Renamed, path: PathBuf::from("a.lua"), destination: Some(PathBuf::from("b.lua")) }],
+            change_events(event)
+        );
+    }
+
+    #[test]
+    fn access_events_should_be_ignored() {
+        let event = Event::new(EventKind::Access(AccessKind::Any))
+            .add_path(PathBuf::from("a.lua"));
+
+        assert!(change_events(event).is_empty());
+        assert_eq!(1, change_events(Event::new(EventKind::Create(CreateKind::File)).add_path(PathBuf::from("a.lua"))).len());
+    }
+
+    #[test]
+    fn watcher_should_report_created_files() {
+        let tmp_dir = temp_root(Permission::ReadWrite);
+        let watcher = Directory::from(tmp_dir.path()).watch(true).unwrap();
+        std::fs::write(tmp_dir.path().join("sprite.png"), "qwe").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut changes = Vec::new();
+        while changes.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            changes.extend(watcher.poll().unwrap());
+        }
+
+        assert!(changes.iter().any(|change| change.kind == ChangeKind::Created && change.path.ends_with("sprite.png")));
+    }
+}